@@ -0,0 +1,133 @@
+// Emulator throughput. Run with `cargo bench`; criterion reports instructions per second for
+// the CPU and frames per second (elem/s) for the PPU and the whole console.
+//
+// Out of the box, frame/ only measures nestest: no homebrew ROMs ship with the repo.
+// benches/roms/ is a gitignored drop-in directory. Copy .nes files there (freely licensed
+// NROM homebrew works best) and each one gets its own frame/<file stem> benchmark after
+// nestest. ROMs that don't parse or need a mapper we don't have yet are skipped with a note
+// on stderr.
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rs6502::cartridge::Cartridge;
+use rs6502::cpu::CPU;
+use rs6502::input::Controller;
+use rs6502::memory::FlatMemory;
+use rs6502::nes::Nes;
+use rs6502::ppu::PPU;
+
+const ROM_DIR: &str = "benches/roms";
+
+const INSTRUCTIONS: u64 = 10_000;
+
+// frames to run before measuring, so ROMs are past their boot code
+const WARMUP_FRAMES: u32 = 30;
+
+// a loop touching most addressing modes, the stack and a subroutine call
+#[rustfmt::skip]
+const PROGRAM: [u8; 34] = [
+    0xA2, 0x00,             // 8000  LDX #$00
+    0xB5, 0x10,             // 8002  LDA $10,X
+    0x7D, 0x00, 0x02,       // 8004  ADC $0200,X
+    0x9D, 0x00, 0x03,       // 8007  STA $0300,X
+    0xB1, 0x20,             // 800A  LDA ($20),Y
+    0x49, 0x5A,             // 800C  EOR #$5A
+    0x0A,                   // 800E  ASL A
+    0x26, 0x30,             // 800F  ROL $30
+    0x48,                   // 8011  PHA
+    0x68,                   // 8012  PLA
+    0xE6, 0x31,             // 8013  INC $31
+    0xC9, 0x80,             // 8015  CMP #$80
+    0x24, 0x32,             // 8017  BIT $32
+    0x20, 0x20, 0x80,       // 8019  JSR $8020
+    0xE8,                   // 801C  INX
+    0x4C, 0x02, 0x80,       // 801D  JMP $8002
+    0x88,                   // 8020  DEY
+    0x60,                   // 8021  RTS
+];
+
+fn cpu(c: &mut Criterion) {
+    let mut cpu = CPU::new(FlatMemory::from_rom(&PROGRAM).unwrap());
+    cpu.reset();
+
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    group.bench_function("dispatch", |b| {
+        b.iter(|| {
+            for _ in 0..INSTRUCTIONS {
+                // the first clock of an instruction runs it, the rest only burn cycles
+                while cpu.clock() == 0 {}
+            }
+        })
+    });
+    group.finish();
+}
+
+fn ppu_frame(ppu: &mut PPU, cart: &Cartridge) {
+    let frame = ppu.frame_number();
+    while ppu.frame_number() == frame {
+        ppu.clock(cart);
+    }
+}
+
+fn ppu(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ppu");
+    group.throughput(Throughput::Elements(1));
+    for (name, mask) in [("rendering_off", 0x00), ("rendering_on", 0x1E)] {
+        let mut cart = Cartridge::new("nestest.nes".to_string()).unwrap();
+        let mut ppu = PPU::new(cart.region);
+        ppu.cpu_write(0x0001, mask, &mut cart);
+        group.bench_function(name, |b| b.iter(|| ppu_frame(&mut ppu, &cart)));
+    }
+    group.finish();
+}
+
+fn warmed_up(cartridge: Cartridge) -> Nes {
+    let mut nes = Nes::new(cartridge);
+    nes.reset();
+    for _ in 0..WARMUP_FRAMES {
+        nes.run_frame();
+    }
+    nes
+}
+
+fn roms() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(ROM_DIR) else {
+        return Vec::new();
+    };
+    let mut roms: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes")))
+        .collect();
+    roms.sort();
+    roms
+}
+
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(1));
+
+    // Start runs the official opcode tests, so the CPU is doing real work every frame
+    let mut nestest = warmed_up(Cartridge::new("nestest.nes".to_string()).unwrap());
+    nestest.cpu.bus.pad_mut(0).unwrap().buttons = Controller::START;
+    group.bench_function("nestest", |b| b.iter(|| nestest.run_frame()));
+
+    for path in roms() {
+        let cartridge = match fs::read(&path).map_err(|e| e.to_string()).and_then(|rom| Cartridge::parse(&rom)) {
+            Ok(cartridge) => cartridge,
+            Err(e) => {
+                eprintln!("skipping {}: {e}", path.display());
+                continue;
+            },
+        };
+        let mut nes = warmed_up(cartridge);
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        group.bench_function(name, |b| b.iter(|| nes.run_frame()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cpu, ppu, frame);
+criterion_main!(benches);
@@ -0,0 +1,149 @@
+// Runs a raw binary linked with linker.cfg on a bare 6502, with a few memory-mapped
+// ports so programs can talk to the outside world:
+//
+//   $7F00  write: put a character on stdout
+//   $7F01  read:  get a character from stdin, $FF at end of input
+//   $7F02  write: exit with the written value as the status code
+//
+// usage: run6502 <rom.bin> [--cpu 6502|65c02|2a03] [--max-cycles n]
+use std::fs;
+use std::io::{self, BufWriter, Read, StdinLock, StdoutLock, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use rs6502::bus::CpuBus;
+use rs6502::cpu::{Variant, CPU};
+use rs6502::diagnostics::Diagnostic;
+use rs6502::memory::FlatMemory;
+
+// between the end of RAM and the start of ROM in linker.cfg
+const CHAR_OUT: u16 = 0x7F00;
+const CHAR_IN: u16 = 0x7F01;
+const EXIT: u16 = 0x7F02;
+
+const END_OF_INPUT: u8 = 0xFF;
+
+// the status codes the runner itself uses; a program can return anything
+const STATUS_STOPPED: u8 = 1;
+const STATUS_USAGE: u8 = 2;
+
+struct Sim {
+    memory: FlatMemory,
+    stdin: StdinLock<'static>,
+    stdout: BufWriter<StdoutLock<'static>>,
+    exit: Option<u8>,
+}
+
+impl CpuBus for Sim {
+    fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            CHAR_IN => {
+                let _ = self.stdout.flush();
+                let mut byte = [END_OF_INPUT];
+                match self.stdin.read(&mut byte) {
+                    Ok(1) => byte[0],
+                    _ => END_OF_INPUT,
+                }
+            },
+            _ => self.memory.read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            CHAR_OUT => {
+                let _ = self.stdout.write_all(&[data]);
+            },
+            EXIT => self.exit = Some(data),
+            _ => self.memory.write(addr, data),
+        }
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        match addr {
+            CHAR_OUT | CHAR_IN | EXIT => 0x00,
+            _ => self.memory.peek(addr),
+        }
+    }
+
+    fn report(&self, diagnostic: Diagnostic) {
+        self.memory.report(diagnostic);
+    }
+}
+
+struct Args {
+    rom: String,
+    variant: Variant,
+    max_cycles: Option<u64>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut rom = None;
+    let mut variant = Variant::NMOS6502;
+    let mut max_cycles = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cpu" => {
+                let cpu = args.next().ok_or("--cpu needs one of 6502, 65c02, 2a03")?;
+                variant = cpu.parse()?;
+            },
+            "--max-cycles" => {
+                let n = args.next().ok_or("--max-cycles needs a number")?;
+                max_cycles = Some(n.parse().map_err(|_| format!("bad cycle count {n}"))?);
+            },
+            _ => rom = Some(arg),
+        }
+    }
+
+    let rom = rom.ok_or("usage: run6502 <rom.bin> [--cpu 6502|65c02|2a03] [--max-cycles n]")?;
+    Ok(Args { rom, variant, max_cycles })
+}
+
+fn run(args: Args) -> Result<u8, String> {
+    let image = fs::read(&args.rom).map_err(|e| format!("{}: {e}", args.rom))?;
+    let mut memory = FlatMemory::from_rom(&image)?;
+    memory.diagnostics = Some(Arc::new(|diagnostic: Diagnostic| eprintln!("{diagnostic}")));
+
+    let sim = Sim {
+        memory,
+        stdin: io::stdin().lock(),
+        stdout: BufWriter::new(io::stdout().lock()),
+        exit: None,
+    };
+
+    let mut cpu = CPU::new(sim);
+    cpu.variant = args.variant;
+    cpu.reset();
+
+    let status = loop {
+        if let Some(status) = cpu.bus.exit {
+            break status;
+        }
+        if cpu.halted() {
+            eprintln!("CPU stopped at {:04X}", cpu.pc);
+            break STATUS_STOPPED;
+        }
+        let cycles = cpu.clock_count as u64;
+        if args.max_cycles.is_some_and(|max| cycles >= max) {
+            eprintln!("gave up after {cycles} cycles at {:04X}", cpu.pc);
+            break STATUS_STOPPED;
+        }
+
+        cpu.clock();
+    };
+
+    let _ = cpu.bus.stdout.flush();
+    Ok(status)
+}
+
+fn main() -> ExitCode {
+    match parse_args().and_then(run) {
+        Ok(status) => ExitCode::from(status),
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(STATUS_USAGE)
+        },
+    }
+}
@@ -1,48 +1,265 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use crate::cartridge::Cartridge;
+use crate::diagnostics::{Diagnostic, DiagnosticHandler};
+use crate::input::{Controller, InputDevice, InputType};
 use crate::memory::Memory;
 use crate::ppu::PPU;
+use crate::region::Region;
+
+// everything the CPU sees of the machine around it
+pub trait CpuBus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, data: u8);
+    // read without side effects, for debuggers and tracing
+    fn peek(&mut self, addr: u16) -> u8;
+
+    // NMI is edge triggered: returns true once per edge and acknowledges it
+    fn nmi_pending(&mut self) -> bool {
+        false
+    }
+
+    // IRQ is level triggered: held until the device is serviced
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // page to copy to OAM, if a sprite DMA is waiting to halt the CPU
+    fn oam_dma(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn report(&self, _diagnostic: Diagnostic) {}
 
+    // either side of every CPU cycle, so devices clocked alongside the CPU can catch up
+    // before the access lands and after it's done
+    fn start_cycle(&mut self) {}
+
+    fn end_cycle(&mut self) {}
+
+    // (scanline, dot) for trace logs, on machines that have a PPU
+    fn ppu_position(&mut self) -> (i32, i32) {
+        (0, 0)
+    }
+}
+
+#[derive(Clone)]
 pub struct Bus {
     pub memory: Memory,
-    pub cartridge: Rc<RefCell<Cartridge>>,
-    pub ppu: Rc<RefCell<PPU>>,
-    pub controller: [u8; 2],
-    pub controller_state: [u8; 2],
+    pub cartridge: Cartridge,
+    pub ppu: PPU,
+    // $4016 and $4017, each empty or with a device plugged in
+    pub ports: [Option<Box<dyn InputDevice>>; 2],
+    // the last value on the CPU data bus, which is what bits nothing drives read back as
+    pub open_bus: u8,
+    // page written to $4014, waiting for the CPU to halt and copy it to OAM
+    pub oam_dma: Option<u8>,
+    pub diagnostics: Option<DiagnosticHandler>,
+    region: Region,
+    // master clocks for the first half of a CPU cycle and for the rest, from the region
+    cycle_halves: (u64, u64),
+    // master clocks since power on, and how far the PPU has been run. The PPU only runs
+    // when the CPU touches it or when next_event, the next time it raises NMI or finishes
+    // a frame, comes around.
+    pub master_clock: u64,
+    pub ppu_clock: u64,
+    pub next_event: u64,
+    // copied from the PPU as it runs; reading $2002 can still cancel the NMI until the CPU
+    // takes it
+    pub nmi: bool,
+    pub frame_complete: bool,
 }
 
 impl Bus {
+    pub fn new(cartridge: Cartridge, ppu: PPU) -> Bus {
+        let region = ppu.region();
+        let ports = cartridge.input.devices().map(Some);
+        Bus {
+            memory: Memory::new(),
+            cartridge,
+            region,
+            cycle_halves: Self::cycle_halves(region),
+            ppu,
+            ports,
+            open_bus: 0,
+            oam_dma: None,
+            diagnostics: None,
+            master_clock: 0,
+            ppu_clock: 0,
+            next_event: 0,
+            nmi: false,
+            frame_complete: false,
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.sync();
+        self.region = region;
+        self.cycle_halves = Self::cycle_halves(region);
+        self.ppu.set_region(region);
+        self.sync();
+    }
+
+    fn cycle_halves(region: Region) -> (u64, u64) {
+        let divider = region.cpu_divider();
+        (divider / 2, divider - divider / 2)
+    }
+
+    // run the PPU up to the master clock and work out when it next needs to run
+    pub fn sync(&mut self) {
+        let divider = self.region.ppu_divider();
+        let dots = self.master_clock.saturating_sub(self.ppu_clock) / divider;
+        self.ppu.run(dots, &self.cartridge);
+        self.ppu_clock += dots * divider;
+
+        self.nmi = self.ppu.nmi;
+        self.frame_complete |= std::mem::take(&mut self.ppu.frame_complete);
+        self.next_event = self.ppu_clock + self.ppu.dots_to_next_event() * divider;
+    }
+
     pub fn cpu_write(&mut self, addr: u16, data: u8) {
-        if self.cartridge.borrow_mut().cpu_write(addr, data) {
+        self.open_bus = data;
+
+        if self.cartridge.cpu_write(addr, data) {
             // done
         } else if addr <= 0x1FFF {
             self.memory.set_byte(addr & 0x07FF, data);
-        } else if addr >= 0x2000 && addr <= 0x3FFF {
-            self.ppu.borrow_mut().cpu_write(addr & 0x0007, data);
-        } else if addr >= 0x4016 && addr <= 0x4017 {
-            let idx = (addr & 0x0001) as usize;
-            self.controller_state[idx] = self.controller[idx];
+        } else if (0x2000..=0x3FFF).contains(&addr) {
+            self.sync();
+            self.ppu.cpu_write(addr & 0x0007, data, &mut self.cartridge);
+        } else if addr == 0x4014 {
+            self.oam_dma = Some(data);
+        } else if addr == 0x4016 {
+            // one strobe line goes to both ports
+            for device in self.ports.iter_mut().flatten() {
+                device.strobe(data & 0x01 != 0);
+            }
+        } else if (0x4000..=0x401F).contains(&addr) {
+            // the APU (including $4017) and the CPU test registers, which we don't have yet
+        } else {
+            self.report(Diagnostic::UnmappedWrite(addr, data));
         }
     }
 
     pub fn cpu_read(&mut self, addr: u16, read_only: bool) -> u8 {
-        let (was_read, data) = self.cartridge.borrow().cpu_read(addr);
-        if was_read {
-            data
-        } else if addr <= 0x1FFF {
+        // nothing on the cartridge maps below $6000, so RAM doesn't need to ask it first
+        let data = if addr <= 0x1FFF {
             self.memory.get_byte(addr & 0x07FF)
-        } else if addr >= 0x2000 && addr <= 0x3FFF {
-            self.ppu.borrow_mut().cpu_read(addr & 0x0007, read_only)
-        } else if addr >= 0x4016 && addr <= 0x4017 {
-            let idx = (addr & 0x0001) as usize;
-            let data = ((self.controller_state[idx] & 0x80) > 0) as u8;
-            self.controller_state[idx] <<= 1;
+        } else if let (true, data) = self.cartridge.cpu_read(addr) {
+            data
+        } else {
+            self.register_read(addr, read_only)
+        };
+
+        if !read_only {
+            self.open_bus = data;
+        }
+        data
+    }
 
+    // everything that isn't memory, kept out of line so the common reads stay short
+    #[inline(never)]
+    fn register_read(&mut self, addr: u16, read_only: bool) -> u8 {
+        if (0x2000..=0x3FFF).contains(&addr) {
+            self.sync();
+            let data = self.ppu.cpu_read(addr & 0x0007, read_only, &self.cartridge);
+            // a $2002 read can put the vblank flag and NMI back down
+            self.sync();
             data
+        } else if (0x4016..=0x4017).contains(&addr) {
+            self.sync();
+            let device = self.ports[(addr & 0x0001) as usize].as_mut();
+            let data = device.map_or(0x00, |device| device.read(&self.ppu, read_only));
+            (self.open_bus & 0xE0) | (data & 0x1F)
+        } else if (0x4000..=0x401F).contains(&addr) {
+            // nothing here drives the bus yet, so reads see the last value on it
+            self.open_bus
         } else {
+            if !read_only {
+                self.report(Diagnostic::UnmappedRead(addr));
+            }
             0x00
         }
     }
+
+    // puts a device in port 0 ($4016) or 1 ($4017), handing back whatever was there
+    pub fn plug(&mut self, port: usize, device: Box<dyn InputDevice>) -> Option<Box<dyn InputDevice>> {
+        self.ports[port].replace(device)
+    }
+
+    // swap out both ports for the devices of another setup
+    pub fn set_input(&mut self, input: InputType) {
+        self.ports = input.devices().map(Some);
+    }
+
+    pub fn unplug(&mut self, port: usize) -> Option<Box<dyn InputDevice>> {
+        self.ports[port].take()
+    }
+
+    // pad 1-4 as 0-3, wherever it's plugged in: odd pads are on $4016 and even ones on
+    // $4017, with pads 3 and 4 behind an adapter
+    pub fn pad_mut(&mut self, pad: usize) -> Option<&mut Controller> {
+        self.ports[pad % 2].as_deref_mut()?.pad_mut(pad / 2)
+    }
+
+    // the device in a port, if it's a T
+    pub fn device_mut<T: InputDevice>(&mut self, port: usize) -> Option<&mut T> {
+        let device: &mut dyn std::any::Any = self.ports[port].as_deref_mut()?;
+        device.downcast_mut()
+    }
+}
+
+impl CpuBus for Bus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.cpu_read(addr, false)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.cpu_write(addr, data);
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.cpu_read(addr, true)
+    }
+
+    fn nmi_pending(&mut self) -> bool {
+        if self.nmi {
+            self.nmi = false;
+            self.ppu.nmi = false;
+            return true;
+        }
+        false
+    }
+
+    fn oam_dma(&mut self) -> Option<u8> {
+        self.oam_dma.take()
+    }
+
+    fn report(&self, diagnostic: Diagnostic) {
+        if let Some(handler) = &self.diagnostics {
+            handler(diagnostic);
+        }
+    }
+
+    fn ppu_position(&mut self) -> (i32, i32) {
+        self.sync();
+        (self.ppu.scanline(), self.ppu.dot())
+    }
+
+    // the access happens halfway through the cycle
+    fn start_cycle(&mut self) {
+        self.master_clock += self.cycle_halves.0;
+        if self.master_clock >= self.next_event {
+            self.sync();
+        }
+    }
+
+    fn end_cycle(&mut self) {
+        self.master_clock += self.cycle_halves.1;
+        if self.master_clock >= self.next_event {
+            self.sync();
+        }
+    }
 }
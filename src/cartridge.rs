@@ -5,7 +5,9 @@ use nom::number::complete::u8;
 use nom::IResult;
 use nom::error::Error;
 
+use crate::input::InputType;
 use crate::mapper::Mapper;
+use crate::region::Region;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mirror {
@@ -17,60 +19,121 @@ pub enum Mirror {
 pub struct Cartridge {
     v_prg_memory: Vec<u8>,
     v_chr_memory: Vec<u8>,
+    // 8KB at $6000-$7FFF; NROM boards don't have it, but test ROMs report through it and
+    // nothing else minds it being there
+    v_prg_ram: Vec<u8>,
     mapper: Mapper,
     pub mirror: Mirror,
+    pub region: Region,
+    pub input: InputType,
+}
+
+// the 16 byte iNES header, less the fields nothing reads yet
+struct Header {
+    prg_banks: u8,
+    chr_banks: u8,
+    flags_6: u8,
+    flags_7: u8,
+    flags_9: u8,
+    flags_12: u8,
+    flags_15: u8,
+}
+
+fn header(i: &[u8]) -> IResult<&[u8], Header> {
+    let (i, _) = tag(b"NES\x1A")(i)?;
+    let (i, prg_banks) = u8(i)?;
+    let (i, chr_banks) = u8(i)?;
+    let (i, flags_6) = u8(i)?; // mapper1
+    let (i, flags_7) = u8(i)?; // mapper2
+    let (i, _size_prg_ram) = u8(i)?;
+    let (i, flags_9) = u8(i)?; // tv_system1
+    let (i, _flags_10) = u8(i)?; // tv_system2
+    let (i, _flags_11) = u8(i)?;
+    let (i, flags_12) = u8(i)?; // NES 2.0 timing
+    let (i, _) = take(2usize)(i)?;
+    let (i, flags_15) = u8(i)?; // NES 2.0 input
+
+    Ok((i, Header { prg_banks, chr_banks, flags_6, flags_7, flags_9, flags_12, flags_15 }))
 }
 
 impl Cartridge {
-    pub fn new(filename: String) -> Cartridge {
-        let binding = fs::read(filename).expect("can't read cart");
-        let buf: &[u8] = binding.as_slice();
-        let cart = Cartridge::parse(buf);
-        cart.expect("can't parse cart").1
+    pub fn new(filename: String) -> Result<Cartridge, String> {
+        let rom = fs::read(&filename).map_err(|e| format!("{filename}: {e}"))?;
+        Cartridge::parse(&rom)
     }
 
-    pub fn parse(i: &[u8]) -> IResult<&[u8], Self> {
-        let (i, _) = tag::<&[u8; 4], &[u8], Error<&[u8]>>(b"NES\x1A")(i).expect("nes");
-        let (i, prg_banks) = u8::<&[u8], Error<&[u8]>>(i).expect("prg_banks");
-        let (i, chr_banks) = u8::<&[u8], Error<&[u8]>>(i).expect("chr_banks");
-        let (i, flags_6) = u8::<&[u8], Error<&[u8]>>(i).expect("flags_6"); // mapper1
-        let (i, flags_7) = u8::<&[u8], Error<&[u8]>>(i).expect("flags_7"); // mapper2
-        let (i, _size_prg_ram) = u8::<&[u8], Error<&[u8]>>(i).expect("size_prg_ram");
-        let (i, _flags_9) = u8::<&[u8], Error<&[u8]>>(i).expect("_flags_9"); // tv_system1
-        let (i, _flags_10) = u8::<&[u8], Error<&[u8]>>(i).expect("_flags_11"); // tv_system2
-        let (i, _) = take::<usize, &[u8], Error<&[u8]>>(5usize)(i).expect("unused");
-        let (i, prg) = take::<usize, &[u8], Error<&[u8]>>(0x4000 * prg_banks as usize)(i).expect("prg");
-        let (i, chr) = take::<usize, &[u8], Error<&[u8]>>(0x2000 * chr_banks as usize)(i).expect("chr");
-
-        let n_mapper_id = (flags_6 >> 4) | (flags_7 & 0xF0);
+    // a bad header, a mapper we don't have or a short file is an error
+    pub fn parse(rom: &[u8]) -> Result<Cartridge, String> {
+        let (i, h) = header(rom).map_err(|_| "not an iNES image".to_string())?;
+
+        let n_mapper_id = (h.flags_6 >> 4) | (h.flags_7 & 0xF0);
         if n_mapper_id != 0 {
-            panic!("unsupported mapper ID {}", n_mapper_id)
+            return Err(format!("unsupported mapper ID {n_mapper_id}"));
         }
-        let mirror = if flags_6 & 0x01 > 0 { Mirror::VERTICAL } else { Mirror::HORIZONTAL };
 
-        let cart = Cartridge {
-            v_prg_memory: prg.to_vec(),
-            v_chr_memory: chr.to_vec(),
-            mapper: Mapper::new(prg_banks, chr_banks),
-            mirror,
+        let prg_size = 0x4000 * h.prg_banks as usize;
+        let chr_size = 0x2000 * h.chr_banks as usize;
+        let truncated = |_: nom::Err<Error<&[u8]>>| {
+            format!("truncated image, {} of {} bytes", rom.len(), 16 + prg_size + chr_size)
+        };
+        let (i, prg) = take(prg_size)(i).map_err(truncated)?;
+        let (_, chr) = take(chr_size)(i).map_err(truncated)?;
+
+        let mirror = if h.flags_6 & 0x01 > 0 { Mirror::VERTICAL } else { Mirror::HORIZONTAL };
+
+        let nes2 = h.flags_7 & 0x0C == 0x08;
+        let region = if nes2 {
+            match h.flags_12 & 0x03 {
+                0x01 => Region::PAL,
+                0x03 => Region::DENDY,
+                _ => Region::NTSC, // 0x02 is multi-region, which runs fine as NTSC
+            }
+        } else if h.flags_9 & 0x01 > 0 {
+            Region::PAL
+        } else {
+            Region::NTSC
         };
 
-        Ok((i, cart))
+        let input = if nes2 { InputType::from_nes2(h.flags_15 & 0x3F) } else { InputType::STANDARD };
+
+        // carts without CHR ROM have 8KB of CHR RAM instead
+        let chr = if h.chr_banks == 0 { vec![0; 0x2000] } else { chr.to_vec() };
+
+        Ok(Cartridge {
+            v_prg_memory: prg.to_vec(),
+            v_chr_memory: chr,
+            v_prg_ram: vec![0; 0x2000],
+            mapper: Mapper::new(h.prg_banks, h.chr_banks),
+            mirror,
+            region,
+            input,
+        })
     }
 
     pub fn cpu_read(&self, addr: u16) -> (bool, u8) {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            return (true, self.v_prg_ram[(addr & 0x1FFF) as usize]);
+        }
+
         match self.mapper.cpu_map_read(addr) {
             (true, mapped_addr) => {
-                (true, self.v_prg_memory[mapped_addr as usize])
+                (true, self.v_prg_memory.get(mapped_addr as usize).copied().unwrap_or(0))
             }
             _ => (false, 0)
         }
     }
 
     pub fn cpu_write(&mut self, addr: u16, data: u8) -> bool {
+        if (0x6000..=0x7FFF).contains(&addr) {
+            self.v_prg_ram[(addr & 0x1FFF) as usize] = data;
+            return true;
+        }
+
         match self.mapper.cpu_map_write(addr) {
             (true, mapped_addr) => {
-                self.v_prg_memory[mapped_addr as usize] = data;
+                if let Some(byte) = self.v_prg_memory.get_mut(mapped_addr as usize) {
+                    *byte = data;
+                }
                 true
             }
             _ => false
@@ -80,7 +143,7 @@ impl Cartridge {
     pub fn ppu_read(&self, addr: u16) -> (bool, u8) {
         match self.mapper.ppu_map_read(addr) {
             (true, mapped_addr) => {
-                return (true, self.v_chr_memory[mapped_addr as usize])
+                (true, self.v_chr_memory.get(mapped_addr as usize).copied().unwrap_or(0))
             }
             _ => (false, 0)
         }
@@ -89,7 +152,9 @@ impl Cartridge {
     pub fn ppu_write(&mut self, addr: u16, data: u8) -> bool {
         match self.mapper.ppu_map_write(addr) {
             (true, mapped_addr) => {
-                self.v_chr_memory[mapped_addr as usize] = data;
+                if let Some(byte) = self.v_chr_memory.get_mut(mapped_addr as usize) {
+                    *byte = data;
+                }
                 true
             }
             _ => false
@@ -1,21 +1,21 @@
-use std::{fmt::Write, ops::Sub};
+use std::io::{self, Write as _};
+use std::{fmt::Write, ops::Sub, str::FromStr};
 use bitflags::bitflags;
 
-use crate::{bus::Bus, memory::Memory};
+use crate::bus::{Bus, CpuBus};
 
+pub mod disasm;
 pub mod instr;
 use instr::{
-    AddrMode::{ABS, ABX, ABY, ACC, IDX, IDY, IMM, IMP, IND, REL, ZP0, ZPX, ZPY},
-    Instr,
+    AddrMode::{self, ABS, ABX, ABY, ACC, IAX, IDX, IDY, IMM, IMP, IND, NUL, REL, ZP0, ZPI, ZPR, ZPX, ZPY},
+    Instr, INSTRUCTIONS, INSTRUCTIONS_65C02,
     Operation::{
-        ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRK, BVC, BVS,
+        self, ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRK, BVC, BVS,
         CLC, CLD, CLV, CMP, CPX, CPY, DCP, DEC, DEX, DEY, EOR, IGN, INC, INX, INY, ISB, JMP,
         JSR, LAX, LDA, LDX, LDY, LSR, NOP, ORA, PHA, PHP, PLA, PLP, RLA, ROL, ROR, RRA, RTI,
         RTS, SAX, SBC, SEC, SED, SEI, SKB, SLO, SRE, STA, STX, STY, TAX, TAY, TSX,
-        TXA, TXS, TYA, XXX, TAS, SXA, CLI,
-
-        // unimplemented
-        // AHX, ALR, ANC, ARR, AXS, LAS, SYA, XAA
+        TXA, TXS, TYA, XXX, TAS, SXA, CLI, AHX, ALR, ANC, ARR, AXS, LAS, SYA, XAA,
+        BRA, PHX, PHY, PLX, PLY, STZ, TRB, TSB, WAI, STP, BBR, BBS, RMB, SMB, UND,
     },
 };
 
@@ -33,7 +33,83 @@ bitflags! {
     }
 }
 
-pub struct CPU {
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    // the NES CPU, a 6502 with the decimal mode circuitry cut
+    #[default]
+    RP2A03,
+    NMOS6502,
+    WDC65C02,
+}
+
+impl Variant {
+    pub fn has_decimal_mode(&self) -> bool {
+        !matches!(self, Variant::RP2A03)
+    }
+
+    pub fn instructions(&self) -> &'static [Instr; 256] {
+        match self {
+            Variant::WDC65C02 => &INSTRUCTIONS_65C02,
+            _ => &INSTRUCTIONS,
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "2a03" => Ok(Variant::RP2A03),
+            "6502" => Ok(Variant::NMOS6502),
+            "65c02" => Ok(Variant::WDC65C02),
+            x => Err(format!("unknown cpu {x}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    // stopped by a JAM opcode, only a reset gets it going again
+    Halted,
+    // WAI: idle until an interrupt comes in
+    Waiting,
+}
+
+// One exec::<OPCODE, CMOS> per opcode, in opcode order. Only usable inside impl CPU.
+macro_rules! dispatch_table {
+    ($cmos:literal) => {
+        flatten([
+            dispatch_table!(@row $cmos, 0x00), dispatch_table!(@row $cmos, 0x10),
+            dispatch_table!(@row $cmos, 0x20), dispatch_table!(@row $cmos, 0x30),
+            dispatch_table!(@row $cmos, 0x40), dispatch_table!(@row $cmos, 0x50),
+            dispatch_table!(@row $cmos, 0x60), dispatch_table!(@row $cmos, 0x70),
+            dispatch_table!(@row $cmos, 0x80), dispatch_table!(@row $cmos, 0x90),
+            dispatch_table!(@row $cmos, 0xA0), dispatch_table!(@row $cmos, 0xB0),
+            dispatch_table!(@row $cmos, 0xC0), dispatch_table!(@row $cmos, 0xD0),
+            dispatch_table!(@row $cmos, 0xE0), dispatch_table!(@row $cmos, 0xF0),
+        ])
+    };
+    (@row $cmos:literal, $row:literal) => {
+        dispatch_table!(@cols $cmos, $row; 0x0 0x1 0x2 0x3 0x4 0x5 0x6 0x7 0x8 0x9 0xA 0xB 0xC 0xD 0xE 0xF)
+    };
+    (@cols $cmos:literal, $row:literal; $($col:literal)*) => {
+        [$(Self::exec::<{ $row + $col }, $cmos>),*]
+    };
+}
+
+const fn flatten<T: Copy>(rows: [[T; 16]; 16]) -> [T; 256] {
+    let mut table = [rows[0][0]; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = rows[i >> 4][i & 0x0F];
+        i += 1;
+    }
+    table
+}
+
+pub struct CPU<B: CpuBus = Bus> {
     // registers
     pub a: u8,
     pub x: u8,
@@ -41,10 +117,9 @@ pub struct CPU {
     pub pc: u16,
     pub sp: u8,
     pub status: Status,
+    pub variant: Variant,
 
-    // memory
-    pub memory: Memory,
-    pub bus: Bus,
+    pub bus: B,
 
     // state
     pub clock_count: usize,
@@ -54,15 +129,48 @@ pub struct CPU {
     pub instr: Instr,
     pub fetched_data: u8,
     pub disasm: String,
+    pub state: State,
+    // the last read was stalled by DMA
+    pub dma_halted: bool,
+    // nestest.log style line per instruction, while enabled
+    pub trace: Option<Box<dyn io::Write + Send>>,
+    pub trace_enabled: bool,
+}
 
-    // misc
-    pub corrupted: bool,
+// a trace sink can't be shared, so clones start out without one
+impl<B: CpuBus + Clone> Clone for CPU<B> {
+    fn clone(&self) -> Self {
+        CPU {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            pc: self.pc,
+            sp: self.sp,
+            status: self.status,
+            variant: self.variant,
+            bus: self.bus.clone(),
+            clock_count: self.clock_count,
+            cycles_remaining: self.cycles_remaining,
+            abs_addr: self.abs_addr,
+            rel_addr: self.rel_addr,
+            instr: self.instr,
+            fetched_data: self.fetched_data,
+            disasm: self.disasm.clone(),
+            state: self.state,
+            dma_halted: self.dma_halted,
+            trace: None,
+            trace_enabled: false,
+        }
+    }
 }
 
-impl CPU {
+impl<B: CpuBus> CPU<B> {
     const SP_BASE: u16 = 0x0100;
 
-    pub fn new(bus: Bus) -> CPU {
+    const DISPATCH: [fn(&mut Self); 256] = dispatch_table!(false);
+    const DISPATCH_65C02: [fn(&mut Self); 256] = dispatch_table!(true);
+
+    pub fn new(bus: B) -> CPU<B> {
         let mut cpu = CPU {
             a: 0,
             x: 0,
@@ -70,16 +178,19 @@ impl CPU {
             pc: 0xFFFC,
             sp: 0xFD,
             status: Status::empty(),
-            memory: Memory::new(),
+            variant: Variant::default(),
             clock_count: 0,
             cycles_remaining: 0,
             bus,
             abs_addr: 0,
             rel_addr: 0,
-            instr: CPU::INSTRUCTIONS[0x00],
+            instr: INSTRUCTIONS[0x00],
             fetched_data: 0,
             disasm: String::with_capacity(100),
-            corrupted: false,
+            state: State::Running,
+            dma_halted: false,
+            trace: None,
+            trace_enabled: false,
         };
 
         cpu.status.set(Status::I, true);
@@ -93,15 +204,54 @@ impl CPU {
             return 0;
         }
 
-        //self.trace_instr();
-
         let start_cycle = self.clock_count;
 
+        if self.poll_interrupts() {
+            return self.instr_done(start_cycle);
+        }
+
+        if self.state != State::Running {
+            // the bus is stuck, but time keeps passing
+            self.start_cycle();
+            self.end_cycle();
+            return 0;
+        }
+
+        if self.trace_enabled {
+            self.trace_instr();
+        }
+
         self.status.set(Status::U, true);
         let opcode = self.read_instr();
-        self.instr = CPU::INSTRUCTIONS[opcode as usize];
+        let dispatch = match self.variant {
+            Variant::WDC65C02 => &Self::DISPATCH_65C02,
+            _ => &Self::DISPATCH,
+        };
+        dispatch[opcode as usize](self);
 
-        match self.instr.addr_mode() {
+        self.status.set(Status::U, true);
+
+        self.instr_done(start_cycle)
+    }
+
+    // One of these per opcode and instruction set, so the instruction is known at compile
+    // time and both matches below fold down to straight calls.
+    fn exec<const OPCODE: u8, const CMOS: bool>(&mut self) {
+        let instr = const {
+            if CMOS {
+                INSTRUCTIONS_65C02[OPCODE as usize]
+            } else {
+                INSTRUCTIONS[OPCODE as usize]
+            }
+        };
+        self.instr = instr;
+        self.addressing_mode(instr.addr_mode());
+        self.operation(instr.op());
+    }
+
+    #[inline(always)]
+    fn addressing_mode(&mut self, mode: AddrMode) {
+        match mode {
             IMP => self.imp(),
             IMM => self.imm(),
             REL => self.rel(),
@@ -115,9 +265,16 @@ impl CPU {
             ABY => self.aby(),
             ZPX => self.zpx(),
             ZPY => self.zpy(),
+            ZPI => self.zpi(),
+            IAX => self.iax(),
+            ZPR => self.zpr(),
+            NUL => self.nul(),
         }
+    }
 
-        match self.instr.op() {
+    #[inline(always)]
+    fn operation(&mut self, op: Operation) {
+        match op {
             LDA => self.lda(),
             LDX => self.ldx(),
             LDY => self.ldy(),
@@ -186,12 +343,34 @@ impl CPU {
             CLI => self.cli(),
             BRK => self.brk(),
             NOP => self.nop(),
+            ANC => self.anc(),
+            ALR => self.alr(),
+            ARR => self.arr(),
+            AXS => self.axs(),
+            LAS => self.las(),
+            XAA => self.xaa(),
+            AHX => self.ahx(),
+            SYA => self.sya(),
             XXX => self.xxx(),
-            x => panic!("unimplemented op {:?}", x)
+            BRA => self.bra(),
+            PHX => self.phx(),
+            PHY => self.phy(),
+            PLX => self.plx(),
+            PLY => self.ply(),
+            STZ => self.stz(),
+            TRB => self.trb(),
+            TSB => self.tsb(),
+            WAI => self.wai(),
+            STP => self.stp(),
+            BBR => self.bbr(),
+            BBS => self.bbs(),
+            RMB => self.rmb(),
+            SMB => self.smb(),
+            UND => self.und(),
         }
+    }
 
-        self.status.set(Status::U, true);
-
+    fn instr_done(&mut self, start_cycle: usize) -> usize {
         let cycles_ran = self.clock_count - start_cycle;
 
         self.cycles_remaining = cycles_ran;
@@ -199,6 +378,29 @@ impl CPU {
         cycles_ran
     }
 
+    // interrupt lines are only looked at between instructions
+    fn poll_interrupts(&mut self) -> bool {
+        if self.halted() {
+            return false;
+        }
+
+        if self.bus.nmi_pending() {
+            self.nmi();
+            true
+        } else if self.bus.irq_pending() {
+            if self.status.contains(Status::I) {
+                // a masked IRQ still ends WAI, execution just carries on
+                self.state = State::Running;
+                false
+            } else {
+                self.irq();
+                true
+            }
+        } else {
+            false
+        }
+    }
+
     pub fn reset(&mut self) {
         self.a = 0;
         self.x = 0;
@@ -208,9 +410,10 @@ impl CPU {
 
         self.clock_count = 0;
         self.cycles_remaining = 0;
+        self.state = State::Running;
 
-        let lo = self.bus.cpu_read(0xFFFC, true);
-        let hi = self.bus.cpu_read(0xFFFD, true);
+        let lo = self.bus.peek(0xFFFC);
+        let hi = self.bus.peek(0xFFFD);
         self.pc = u16::from_le_bytes([lo, hi]);
 
         // 7 cycles
@@ -224,30 +427,72 @@ impl CPU {
         self.cycles_remaining > 0
     }
 
+    pub fn halted(&self) -> bool {
+        self.state == State::Halted
+    }
+
     pub fn nmi(&mut self) {
+        self.interrupt(0xFFFA);
+    }
+
+    pub fn irq(&mut self) {
+        if !self.status.contains(Status::I) {
+            self.interrupt(0xFFFE);
+        }
+    }
+
+    fn interrupt(&mut self, vector: u16) {
+        if self.halted() {
+            return;
+        }
+        self.state = State::Running;
+
+        // two dummy fetches of the instruction that got pre-empted
+        let _ = self.read(self.pc);
+        let _ = self.read(self.pc);
+
         self.push_u16(self.pc);
 
-        let status = ((self.status | Status::U | Status::I) & !Status::B).bits();
+        let status = ((self.status | Status::U) & !Status::B).bits();
         self.push(status);
+        self.status.set(Status::I, true);
+        self.clear_decimal_on_interrupt();
 
-        self.pc = self.read_u16(0xFFFA);
-
-        // 3 more cycles
-        for _ in 0..3 {
-            self.start_cycle();
-            self.end_cycle();
-        }
+        self.pc = self.read_u16(vector);
     }
 
     pub fn read(&mut self, addr: u16) -> u8 {
+        // DMA can only pull RDY low on a read cycle
+        self.dma_halted = false;
+        if let Some(page) = self.bus.oam_dma() {
+            self.oam_dma(page);
+            self.dma_halted = true;
+        }
+
         self.start_cycle();
-        let val = self.bus.cpu_read(addr, false);
+        let val = self.bus.read(addr);
         self.end_cycle();
         val
     }
 
+    fn oam_dma(&mut self, page: u8) {
+        // one halt cycle, plus one more to line up with a get cycle
+        let halt_cycles = 1 + (self.clock_count & 0x01);
+        for _ in 0..halt_cycles {
+            self.start_cycle();
+            self.end_cycle();
+        }
+
+        for lo in 0..=0xFF {
+            self.start_cycle();
+            let val = self.bus.read(u16::from_le_bytes([lo, page]));
+            self.end_cycle();
+            self.write(0x2004, val);
+        }
+    }
+
     pub fn peek(&mut self, addr: u16) -> u8 {
-        self.bus.cpu_read(addr, true)
+        self.bus.peek(addr)
     }
 
     pub fn read_u16(&mut self, addr: u16) -> u16 {
@@ -264,7 +509,7 @@ impl CPU {
 
     pub fn write(&mut self, addr: u16, data: u8) {
         self.start_cycle();
-        self.bus.cpu_write(addr, data);
+        self.bus.write(addr, data);
         self.end_cycle();
     }
 
@@ -345,35 +590,48 @@ impl CPU {
 
     fn start_cycle(&mut self) {
         self.clock_count = self.clock_count.wrapping_add(1);
+        self.bus.start_cycle();
     }
 
     fn end_cycle(&mut self) {
-        // later
+        self.bus.end_cycle();
     }
 
-    pub fn trace_instr(&mut self) {
-        let pc = self.pc;
-        let acc = self.a;
-        let x = self.x;
-        let y = self.y;
-        let sp = self.sp;
-        let cycle = self.clock_count;
-        let st = (self.status | Status::U).sub(Status::B); // remove U and B
+    pub fn trace_to(&mut self, out: impl io::Write + Send + 'static) {
+        self.trace = Some(Box::new(out));
+        self.trace_enabled = true;
+    }
 
-        let ppu_cycle = 0; // todo
-        let ppu_scanline= 0; // todo
+    pub fn trace_instr(&mut self) {
+        let line = self.trace_line();
+        if let Some(out) = self.trace.as_mut() {
+            // a broken trace sink shouldn't take the emulator down with it
+            if writeln!(out, "{line}").is_err() {
+                self.trace_enabled = false;
+            }
+        }
+    }
 
-        println!("{:<50} A:{acc:02X} X:{x:02X} Y:{y:02X} P:{st:02X} SP:{sp:02X} PPU:{ppu_cycle:3},{ppu_scanline:3} CYC:{cycle}", self.disassemble(pc));
+    // the instruction about to run, in nestest.log format:
+    // C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+    pub fn trace_line(&mut self) -> String {
+        let regs = disasm::Registers { x: self.x, y: self.y };
+        let line = disasm::disassemble(&mut self.bus, self.pc, self.variant, Some(regs)).to_string();
+        let st = (self.status | Status::U).sub(Status::B);
+        let (scanline, dot) = self.bus.ppu_position();
+
+        format!(
+            "{line:<48}A:{:02X} X:{:02X} Y:{:02X} P:{st:02X} SP:{:02X} PPU:{scanline:>3},{dot:>3} CYC:{}",
+            self.a, self.x, self.y, self.sp, self.clock_count
+        )
     }
 
     pub fn disassemble(&mut self, pc: u16) -> &str {
-        self.disasm.clear();
+        let regs = disasm::Registers { x: self.x, y: self.y };
+        let line = disasm::disassemble(&mut self.bus, pc, self.variant, Some(regs));
 
-        let opcode = self.peek(pc);
-        let instr = CPU::INSTRUCTIONS[opcode as usize].op();
-
-        let _ = write!(self.disasm, "{pc:04X}  {opcode:02X} ");
-        let _ = write!(self.disasm, "        {instr:?}");
+        self.disasm.clear();
+        let _ = write!(self.disasm, "{line}");
 
         &self.disasm
     }
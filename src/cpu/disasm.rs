@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::bus::CpuBus;
+use crate::cpu::instr::{AddrMode, Instr, Operation};
+use crate::cpu::Variant;
+
+// index registers, for working out effective addresses while the program runs; static
+// dumps go without
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Registers {
+    pub x: u8,
+    pub y: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub unofficial: bool,
+    pub text: String,
+}
+
+impl Line {
+    pub fn len(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.len())
+    }
+}
+
+// nestest.log layout: "C000  4C F5 C5  JMP $C5F5", unofficial opcodes get a '*' in place
+// of the space before the mnemonic
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.bytes.iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(" ");
+        let star = if self.unofficial { '*' } else { ' ' };
+        write!(f, "{:04X}  {bytes:<8} {star}{}", self.addr, self.text)
+    }
+}
+
+pub fn operand_len(mode: AddrMode) -> u16 {
+    match mode {
+        AddrMode::IMP | AddrMode::ACC | AddrMode::NUL => 0,
+        AddrMode::ABS | AddrMode::ABX | AddrMode::ABY | AddrMode::IND | AddrMode::IAX | AddrMode::ZPR => 2,
+        _ => 1,
+    }
+}
+
+pub fn mnemonic(instr: Instr) -> String {
+    match instr.op() {
+        Operation::SKB | Operation::IGN | Operation::UND => "NOP".to_string(),
+        Operation::XXX => "JAM".to_string(),
+        // the bit number is part of the mnemonic
+        Operation::BBR | Operation::BBS | Operation::RMB | Operation::SMB => {
+            format!("{:?}{}", instr.op(), (instr.opcode() >> 4) & 0x07)
+        },
+        op => format!("{op:?}"),
+    }
+}
+
+pub fn is_unofficial(instr: Instr, variant: Variant) -> bool {
+    match instr.op() {
+        Operation::UND => true,
+        Operation::NOP => instr.opcode() != 0xEA,
+        Operation::SBC => variant != Variant::WDC65C02 && instr.opcode() == 0xEB,
+        Operation::SKB | Operation::IGN | Operation::ISB | Operation::DCP | Operation::AXS
+        | Operation::LAS | Operation::LAX | Operation::AHX | Operation::SAX | Operation::XAA
+        | Operation::SXA | Operation::RRA | Operation::TAS | Operation::SYA | Operation::ARR
+        | Operation::SRE | Operation::ALR | Operation::RLA | Operation::ANC | Operation::SLO
+        | Operation::XXX => true,
+        _ => false,
+    }
+}
+
+// Disassemble the instruction at addr. Memory is only peeked, so this is safe to run on a
+// live bus. With registers, operands are followed by the address they resolve to and the
+// value found there, e.g. "LDA ($20),Y = 0300 @ 0305 = 7F".
+pub fn disassemble<B: CpuBus>(bus: &mut B, addr: u16, variant: Variant, regs: Option<Registers>) -> Line {
+    let opcode = bus.peek(addr);
+    let instr = variant.instructions()[opcode as usize];
+    let mode = instr.addr_mode();
+
+    let mut bytes = vec![opcode];
+    for i in 1..=operand_len(mode) {
+        bytes.push(bus.peek(addr.wrapping_add(i)));
+    }
+    let lo = bytes.get(1).copied().unwrap_or(0);
+    let hi = bytes.get(2).copied().unwrap_or(0);
+    let word = u16::from_le_bytes([lo, hi]);
+    let next = addr.wrapping_add(bytes.len() as u16);
+
+    let mut text = mnemonic(instr);
+    let operand = match mode {
+        AddrMode::IMP | AddrMode::NUL => String::new(),
+        AddrMode::ACC => "A".to_string(),
+        AddrMode::IMM => format!("#${lo:02X}"),
+        AddrMode::REL => format!("${:04X}", branch_target(next, lo)),
+        AddrMode::ZP0 => format!("${lo:02X}"),
+        AddrMode::ZPX => format!("${lo:02X},X"),
+        AddrMode::ZPY => format!("${lo:02X},Y"),
+        AddrMode::ABS => format!("${word:04X}"),
+        AddrMode::ABX => format!("${word:04X},X"),
+        AddrMode::ABY => format!("${word:04X},Y"),
+        AddrMode::IND => format!("(${word:04X})"),
+        AddrMode::IAX => format!("(${word:04X},X)"),
+        AddrMode::IDX => format!("(${lo:02X},X)"),
+        AddrMode::IDY => format!("(${lo:02X}),Y"),
+        AddrMode::ZPI => format!("(${lo:02X})"),
+        AddrMode::ZPR => format!("${lo:02X},${:04X}", branch_target(next, hi)),
+    };
+    if !operand.is_empty() {
+        text.push(' ');
+        text.push_str(&operand);
+    }
+
+    if let Some(regs) = regs {
+        text.push_str(&effective(bus, instr, variant, regs, lo, word));
+    }
+
+    Line {
+        addr,
+        bytes,
+        unofficial: is_unofficial(instr, variant),
+        text,
+    }
+}
+
+// Disassemble everything from start up to and including end. Instructions running past end
+// are still decoded whole.
+pub fn disassemble_range<B: CpuBus>(bus: &mut B, start: u16, end: u16, variant: Variant) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = u32::from(start);
+    while addr <= u32::from(end) {
+        let line = disassemble(bus, addr as u16, variant, None);
+        addr += u32::from(line.len());
+        lines.push(line);
+    }
+    lines
+}
+
+fn branch_target(next: u16, offset: u8) -> u16 {
+    next.wrapping_add(offset as i8 as u16)
+}
+
+fn effective<B: CpuBus>(bus: &mut B, instr: Instr, variant: Variant, regs: Registers, lo: u8, word: u16) -> String {
+    match instr.addr_mode() {
+        AddrMode::ZP0 | AddrMode::ZPR => format!(" = {:02X}", bus.peek(lo.into())),
+        AddrMode::ABS => match instr.op() {
+            Operation::JMP | Operation::JSR => String::new(),
+            _ => format!(" = {:02X}", bus.peek(word)),
+        },
+        AddrMode::ZPX => {
+            let addr = lo.wrapping_add(regs.x);
+            format!(" @ {addr:02X} = {:02X}", bus.peek(addr.into()))
+        },
+        AddrMode::ZPY => {
+            let addr = lo.wrapping_add(regs.y);
+            format!(" @ {addr:02X} = {:02X}", bus.peek(addr.into()))
+        },
+        AddrMode::ABX => {
+            let addr = word.wrapping_add(regs.x.into());
+            format!(" @ {addr:04X} = {:02X}", bus.peek(addr))
+        },
+        AddrMode::ABY => {
+            let addr = word.wrapping_add(regs.y.into());
+            format!(" @ {addr:04X} = {:02X}", bus.peek(addr))
+        },
+        AddrMode::IND => {
+            // the NMOS parts never carry into the high byte of the pointer
+            let hi_addr = if variant == Variant::WDC65C02 {
+                word.wrapping_add(1)
+            } else {
+                (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF)
+            };
+            format!(" = {:04X}", peek_u16(bus, word, hi_addr))
+        },
+        AddrMode::IAX => {
+            let ptr = word.wrapping_add(regs.x.into());
+            format!(" @ {ptr:04X} = {:04X}", peek_u16(bus, ptr, ptr.wrapping_add(1)))
+        },
+        AddrMode::IDX => {
+            let ptr = lo.wrapping_add(regs.x);
+            let addr = zp_u16(bus, ptr);
+            format!(" @ {ptr:02X} = {addr:04X} = {:02X}", bus.peek(addr))
+        },
+        AddrMode::IDY => {
+            let base = zp_u16(bus, lo);
+            let addr = base.wrapping_add(regs.y.into());
+            format!(" = {base:04X} @ {addr:04X} = {:02X}", bus.peek(addr))
+        },
+        AddrMode::ZPI => {
+            let addr = zp_u16(bus, lo);
+            format!(" = {addr:04X} = {:02X}", bus.peek(addr))
+        },
+        _ => String::new(),
+    }
+}
+
+fn peek_u16<B: CpuBus>(bus: &mut B, lo_addr: u16, hi_addr: u16) -> u16 {
+    u16::from_le_bytes([bus.peek(lo_addr), bus.peek(hi_addr)])
+}
+
+// zero page pointers wrap within the zero page
+fn zp_u16<B: CpuBus>(bus: &mut B, zp: u8) -> u16 {
+    peek_u16(bus, zp.into(), zp.wrapping_add(1).into())
+}
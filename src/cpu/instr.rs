@@ -1,5 +1,7 @@
+use crate::bus::CpuBus;
 use crate::cpu::Status;
-use crate::cpu::CPU;
+use crate::cpu::{State, Variant, CPU};
+use crate::diagnostics::Diagnostic;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AddrMode {
@@ -8,6 +10,8 @@ pub enum AddrMode {
     ABS, ABX, ABY,
     IND, IDX, IDY,
     REL, ACC, IMP,
+    // 65C02
+    ZPI, IAX, ZPR, NUL,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -17,7 +21,9 @@ pub enum Operation {
     PLP, ROL, ROR, RTI, RTS, SBC, SEC, SED, SEI, STA, STX, STY, TAX, TAY, TSX, TXA, TXS, TYA,
     // "Unofficial" opcodes
     SKB, IGN, ISB, DCP, AXS, LAS, LAX, AHX, SAX, XAA, SXA, RRA, TAS, SYA, ARR, SRE, ALR, RLA, ANC,
-    SLO, XXX
+    SLO, XXX,
+    // 65C02
+    BRA, PHX, PHY, PLX, PLY, STZ, TRB, TSB, WAI, STP, BBR, BBS, RMB, SMB, UND,
 }
 
 // (opcode, Addressing Mode, Operation, cycles taken)
@@ -42,34 +48,58 @@ impl Instr {
     }
 }
 
-use AddrMode::{ABS, ABX, ABY, ACC, IDX, IDY, IMM, IMP, IND, REL, ZP0, ZPX, ZPY};
+use AddrMode::{ABS, ABX, ABY, ACC, IAX, IDX, IDY, IMM, IMP, IND, NUL, REL, ZP0, ZPI, ZPR, ZPX, ZPY};
 use Operation::{
-    ADC, AHX, ALR, ANC, AND, ARR, ASL, AXS, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRK, BVC, BVS, CLC,
-    CLD, CLI, CLV, CMP, CPX, CPY, DCP, DEC, DEX, DEY, EOR, IGN, INC, INX, INY, ISB, JMP, JSR, LAS,
-    LAX, LDA, LDX, LDY, LSR, NOP, ORA, PHA, PHP, PLA, PLP, RLA, ROL, ROR, RRA, RTI, RTS, SAX, SBC,
-    SEC, SED, SEI, SKB, SLO, SRE, STA, STX, STY, SXA, SYA, TAS, TAX, TAY, TSX, TXA, TXS, TYA, XAA,
-    XXX,
+    ADC, AHX, ALR, ANC, AND, ARR, ASL, AXS, BBR, BBS, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRA, BRK,
+    BVC, BVS, CLC, CLD, CLI, CLV, CMP, CPX, CPY, DCP, DEC, DEX, DEY, EOR, IGN, INC, INX, INY, ISB,
+    JMP, JSR, LAS, LAX, LDA, LDX, LDY, LSR, NOP, ORA, PHA, PHP, PHX, PHY, PLA, PLP, PLX, PLY, RLA,
+    RMB, ROL, ROR, RRA, RTI, RTS, SAX, SBC, SEC, SED, SEI, SKB, SLO, SMB, SRE, STA, STP, STX, STY,
+    STZ, SXA, SYA, TAS, TAX, TAY, TRB, TSB, TSX, TXA, TXS, TYA, UND, WAI, XAA, XXX,
 };
 
-impl CPU {
-    pub const INSTRUCTIONS: [Instr; 256] = [
-        Instr(0x00, IMM, BRK, 7), Instr(0x01, IDX, ORA, 6), Instr(0x02, IMP, XXX, 2), Instr(0x03, IDX, SLO, 8), Instr(0x04, ZP0, NOP, 3), Instr(0x05, ZP0, ORA, 3), Instr(0x06, ZP0, ASL, 5), Instr(0x07, ZP0, SLO, 5), Instr(0x08, IMP, PHP, 3), Instr(0x09, IMM, ORA, 2), Instr(0x0A, ACC, ASL, 2), Instr(0x0B, IMM, ANC, 2), Instr(0x0C, ABS, NOP, 4), Instr(0x0D, ABS, ORA, 4), Instr(0x0E, ABS, ASL, 6), Instr(0x0F, ABS, SLO, 6),
-        Instr(0x10, REL, BPL, 2), Instr(0x11, IDY, ORA, 5), Instr(0x12, IMP, XXX, 2), Instr(0x13, IDY, SLO, 8), Instr(0x14, ZPX, NOP, 4), Instr(0x15, ZPX, ORA, 4), Instr(0x16, ZPX, ASL, 6), Instr(0x17, ZPX, SLO, 6), Instr(0x18, IMP, CLC, 2), Instr(0x19, ABY, ORA, 4), Instr(0x1A, IMP, NOP, 2), Instr(0x1B, ABY, SLO, 7), Instr(0x1C, ABX, IGN, 4), Instr(0x1D, ABX, ORA, 4), Instr(0x1E, ABX, ASL, 7), Instr(0x1F, ABX, SLO, 7),
-        Instr(0x20, ABS, JSR, 6), Instr(0x21, IDX, AND, 6), Instr(0x22, IMP, XXX, 2), Instr(0x23, IDX, RLA, 8), Instr(0x24, ZP0, BIT, 3), Instr(0x25, ZP0, AND, 3), Instr(0x26, ZP0, ROL, 5), Instr(0x27, ZP0, RLA, 5), Instr(0x28, IMP, PLP, 4), Instr(0x29, IMM, AND, 2), Instr(0x2A, ACC, ROL, 2), Instr(0x2B, IMM, ANC, 2), Instr(0x2C, ABS, BIT, 4), Instr(0x2D, ABS, AND, 4), Instr(0x2E, ABS, ROL, 6), Instr(0x2F, ABS, RLA, 6),
-        Instr(0x30, REL, BMI, 2), Instr(0x31, IDY, AND, 5), Instr(0x32, IMP, XXX, 2), Instr(0x33, IDY, RLA, 8), Instr(0x34, ZPX, NOP, 4), Instr(0x35, ZPX, AND, 4), Instr(0x36, ZPX, ROL, 6), Instr(0x37, ZPX, RLA, 6), Instr(0x38, IMP, SEC, 2), Instr(0x39, ABY, AND, 4), Instr(0x3A, IMP, NOP, 2), Instr(0x3B, ABY, RLA, 7), Instr(0x3C, ABX, IGN, 4), Instr(0x3D, ABX, AND, 4), Instr(0x3E, ABX, ROL, 7), Instr(0x3F, ABX, RLA, 7),
-        Instr(0x40, IMP, RTI, 6), Instr(0x41, IDX, EOR, 6), Instr(0x42, IMP, XXX, 2), Instr(0x43, IDX, SRE, 8), Instr(0x44, ZP0, NOP, 3), Instr(0x45, ZP0, EOR, 3), Instr(0x46, ZP0, LSR, 5), Instr(0x47, ZP0, SRE, 5), Instr(0x48, IMP, PHA, 3), Instr(0x49, IMM, EOR, 2), Instr(0x4A, ACC, LSR, 2), Instr(0x4B, IMM, ALR, 2), Instr(0x4C, ABS, JMP, 3), Instr(0x4D, ABS, EOR, 4), Instr(0x4E, ABS, LSR, 6), Instr(0x4F, ABS, SRE, 6),
-        Instr(0x50, REL, BVC, 2), Instr(0x51, IDY, EOR, 5), Instr(0x52, IMP, XXX, 2), Instr(0x53, IDY, SRE, 8), Instr(0x54, ZPX, NOP, 4), Instr(0x55, ZPX, EOR, 4), Instr(0x56, ZPX, LSR, 6), Instr(0x57, ZPX, SRE, 6), Instr(0x58, IMP, CLI, 2), Instr(0x59, ABY, EOR, 4), Instr(0x5A, IMP, NOP, 2), Instr(0x5B, ABY, SRE, 7), Instr(0x5C, ABX, IGN, 4), Instr(0x5D, ABX, EOR, 4), Instr(0x5E, ABX, LSR, 7), Instr(0x5F, ABX, SRE, 7),
-        Instr(0x60, IMP, RTS, 6), Instr(0x61, IDX, ADC, 6), Instr(0x62, IMP, XXX, 2), Instr(0x63, IDX, RRA, 8), Instr(0x64, ZP0, NOP, 3), Instr(0x65, ZP0, ADC, 3), Instr(0x66, ZP0, ROR, 5), Instr(0x67, ZP0, RRA, 5), Instr(0x68, IMP, PLA, 4), Instr(0x69, IMM, ADC, 2), Instr(0x6A, ACC, ROR, 2), Instr(0x6B, IMM, ARR, 2), Instr(0x6C, IND, JMP, 5), Instr(0x6D, ABS, ADC, 4), Instr(0x6E, ABS, ROR, 6), Instr(0x6F, ABS, RRA, 6),
-        Instr(0x70, REL, BVS, 2), Instr(0x71, IDY, ADC, 5), Instr(0x72, IMP, XXX, 2), Instr(0x73, IDY, RRA, 8), Instr(0x74, ZPX, NOP, 4), Instr(0x75, ZPX, ADC, 4), Instr(0x76, ZPX, ROR, 6), Instr(0x77, ZPX, RRA, 6), Instr(0x78, IMP, SEI, 2), Instr(0x79, ABY, ADC, 4), Instr(0x7A, IMP, NOP, 2), Instr(0x7B, ABY, RRA, 7), Instr(0x7C, ABX, IGN, 4), Instr(0x7D, ABX, ADC, 4), Instr(0x7E, ABX, ROR, 7), Instr(0x7F, ABX, RRA, 7),
-        Instr(0x80, IMM, SKB, 2), Instr(0x81, IDX, STA, 6), Instr(0x82, IMM, SKB, 2), Instr(0x83, IDX, SAX, 6), Instr(0x84, ZP0, STY, 3), Instr(0x85, ZP0, STA, 3), Instr(0x86, ZP0, STX, 3), Instr(0x87, ZP0, SAX, 3), Instr(0x88, IMP, DEY, 2), Instr(0x89, IMM, SKB, 2), Instr(0x8A, IMP, TXA, 2), Instr(0x8B, IMM, XAA, 2), Instr(0x8C, ABS, STY, 4), Instr(0x8D, ABS, STA, 4), Instr(0x8E, ABS, STX, 4), Instr(0x8F, ABS, SAX, 4),
-        Instr(0x90, REL, BCC, 2), Instr(0x91, IDY, STA, 6), Instr(0x92, IMP, XXX, 2), Instr(0x93, IDY, AHX, 6), Instr(0x94, ZPX, STY, 4), Instr(0x95, ZPX, STA, 4), Instr(0x96, ZPY, STX, 4), Instr(0x97, ZPY, SAX, 4), Instr(0x98, IMP, TYA, 2), Instr(0x99, ABY, STA, 5), Instr(0x9A, IMP, TXS, 2), Instr(0x9B, ABY, TAS, 5), Instr(0x9C, ABX, SYA, 5), Instr(0x9D, ABX, STA, 5), Instr(0x9E, ABY, SXA, 5), Instr(0x9F, ABY, AHX, 5),
-        Instr(0xA0, IMM, LDY, 2), Instr(0xA1, IDX, LDA, 6), Instr(0xA2, IMM, LDX, 2), Instr(0xA3, IDX, LAX, 6), Instr(0xA4, ZP0, LDY, 3), Instr(0xA5, ZP0, LDA, 3), Instr(0xA6, ZP0, LDX, 3), Instr(0xA7, ZP0, LAX, 3), Instr(0xA8, IMP, TAY, 2), Instr(0xA9, IMM, LDA, 2), Instr(0xAA, IMP, TAX, 2), Instr(0xAB, IMM, LAX, 2), Instr(0xAC, ABS, LDY, 4), Instr(0xAD, ABS, LDA, 4), Instr(0xAE, ABS, LDX, 4), Instr(0xAF, ABS, LAX, 4),
-        Instr(0xB0, REL, BCS, 2), Instr(0xB1, IDY, LDA, 5), Instr(0xB2, IMP, XXX, 2), Instr(0xB3, IDY, LAX, 5), Instr(0xB4, ZPX, LDY, 4), Instr(0xB5, ZPX, LDA, 4), Instr(0xB6, ZPY, LDX, 4), Instr(0xB7, ZPY, LAX, 4), Instr(0xB8, IMP, CLV, 2), Instr(0xB9, ABY, LDA, 4), Instr(0xBA, IMP, TSX, 2), Instr(0xBB, ABY, LAS, 4), Instr(0xBC, ABX, LDY, 4), Instr(0xBD, ABX, LDA, 4), Instr(0xBE, ABY, LDX, 4), Instr(0xBF, ABY, LAX, 4),
-        Instr(0xC0, IMM, CPY, 2), Instr(0xC1, IDX, CMP, 6), Instr(0xC2, IMM, SKB, 2), Instr(0xC3, IDX, DCP, 8), Instr(0xC4, ZP0, CPY, 3), Instr(0xC5, ZP0, CMP, 3), Instr(0xC6, ZP0, DEC, 5), Instr(0xC7, ZP0, DCP, 5), Instr(0xC8, IMP, INY, 2), Instr(0xC9, IMM, CMP, 2), Instr(0xCA, IMP, DEX, 2), Instr(0xCB, IMM, AXS, 2), Instr(0xCC, ABS, CPY, 4), Instr(0xCD, ABS, CMP, 4), Instr(0xCE, ABS, DEC, 6), Instr(0xCF, ABS, DCP, 6),
-        Instr(0xD0, REL, BNE, 2), Instr(0xD1, IDY, CMP, 5), Instr(0xD2, IMP, XXX, 2), Instr(0xD3, IDY, DCP, 8), Instr(0xD4, ZPX, NOP, 4), Instr(0xD5, ZPX, CMP, 4), Instr(0xD6, ZPX, DEC, 6), Instr(0xD7, ZPX, DCP, 6), Instr(0xD8, IMP, CLD, 2), Instr(0xD9, ABY, CMP, 4), Instr(0xDA, IMP, NOP, 2), Instr(0xDB, ABY, DCP, 7), Instr(0xDC, ABX, IGN, 4), Instr(0xDD, ABX, CMP, 4), Instr(0xDE, ABX, DEC, 7), Instr(0xDF, ABX, DCP, 7),
-        Instr(0xE0, IMM, CPX, 2), Instr(0xE1, IDX, SBC, 6), Instr(0xE2, IMM, SKB, 2), Instr(0xE3, IDX, ISB, 8), Instr(0xE4, ZP0, CPX, 3), Instr(0xE5, ZP0, SBC, 3), Instr(0xE6, ZP0, INC, 5), Instr(0xE7, ZP0, ISB, 5), Instr(0xE8, IMP, INX, 2), Instr(0xE9, IMM, SBC, 2), Instr(0xEA, IMP, NOP, 2), Instr(0xEB, IMM, SBC, 2), Instr(0xEC, ABS, CPX, 4), Instr(0xED, ABS, SBC, 4), Instr(0xEE, ABS, INC, 6), Instr(0xEF, ABS, ISB, 6),
-        Instr(0xF0, REL, BEQ, 2), Instr(0xF1, IDY, SBC, 5), Instr(0xF2, IMP, XXX, 2), Instr(0xF3, IDY, ISB, 8), Instr(0xF4, ZPX, NOP, 4), Instr(0xF5, ZPX, SBC, 4), Instr(0xF6, ZPX, INC, 6), Instr(0xF7, ZPX, ISB, 6), Instr(0xF8, IMP, SED, 2), Instr(0xF9, ABY, SBC, 4), Instr(0xFA, IMP, NOP, 2), Instr(0xFB, ABY, ISB, 7), Instr(0xFC, ABX, IGN, 4), Instr(0xFD, ABX, SBC, 4), Instr(0xFE, ABX, INC, 7), Instr(0xFF, ABX, ISB, 7),
-    ];
+pub const INSTRUCTIONS: [Instr; 256] = [
+    Instr(0x00, IMM, BRK, 7), Instr(0x01, IDX, ORA, 6), Instr(0x02, IMP, XXX, 2), Instr(0x03, IDX, SLO, 8), Instr(0x04, ZP0, NOP, 3), Instr(0x05, ZP0, ORA, 3), Instr(0x06, ZP0, ASL, 5), Instr(0x07, ZP0, SLO, 5), Instr(0x08, IMP, PHP, 3), Instr(0x09, IMM, ORA, 2), Instr(0x0A, ACC, ASL, 2), Instr(0x0B, IMM, ANC, 2), Instr(0x0C, ABS, NOP, 4), Instr(0x0D, ABS, ORA, 4), Instr(0x0E, ABS, ASL, 6), Instr(0x0F, ABS, SLO, 6),
+    Instr(0x10, REL, BPL, 2), Instr(0x11, IDY, ORA, 5), Instr(0x12, IMP, XXX, 2), Instr(0x13, IDY, SLO, 8), Instr(0x14, ZPX, NOP, 4), Instr(0x15, ZPX, ORA, 4), Instr(0x16, ZPX, ASL, 6), Instr(0x17, ZPX, SLO, 6), Instr(0x18, IMP, CLC, 2), Instr(0x19, ABY, ORA, 4), Instr(0x1A, IMP, NOP, 2), Instr(0x1B, ABY, SLO, 7), Instr(0x1C, ABX, IGN, 4), Instr(0x1D, ABX, ORA, 4), Instr(0x1E, ABX, ASL, 7), Instr(0x1F, ABX, SLO, 7),
+    Instr(0x20, ABS, JSR, 6), Instr(0x21, IDX, AND, 6), Instr(0x22, IMP, XXX, 2), Instr(0x23, IDX, RLA, 8), Instr(0x24, ZP0, BIT, 3), Instr(0x25, ZP0, AND, 3), Instr(0x26, ZP0, ROL, 5), Instr(0x27, ZP0, RLA, 5), Instr(0x28, IMP, PLP, 4), Instr(0x29, IMM, AND, 2), Instr(0x2A, ACC, ROL, 2), Instr(0x2B, IMM, ANC, 2), Instr(0x2C, ABS, BIT, 4), Instr(0x2D, ABS, AND, 4), Instr(0x2E, ABS, ROL, 6), Instr(0x2F, ABS, RLA, 6),
+    Instr(0x30, REL, BMI, 2), Instr(0x31, IDY, AND, 5), Instr(0x32, IMP, XXX, 2), Instr(0x33, IDY, RLA, 8), Instr(0x34, ZPX, NOP, 4), Instr(0x35, ZPX, AND, 4), Instr(0x36, ZPX, ROL, 6), Instr(0x37, ZPX, RLA, 6), Instr(0x38, IMP, SEC, 2), Instr(0x39, ABY, AND, 4), Instr(0x3A, IMP, NOP, 2), Instr(0x3B, ABY, RLA, 7), Instr(0x3C, ABX, IGN, 4), Instr(0x3D, ABX, AND, 4), Instr(0x3E, ABX, ROL, 7), Instr(0x3F, ABX, RLA, 7),
+    Instr(0x40, IMP, RTI, 6), Instr(0x41, IDX, EOR, 6), Instr(0x42, IMP, XXX, 2), Instr(0x43, IDX, SRE, 8), Instr(0x44, ZP0, NOP, 3), Instr(0x45, ZP0, EOR, 3), Instr(0x46, ZP0, LSR, 5), Instr(0x47, ZP0, SRE, 5), Instr(0x48, IMP, PHA, 3), Instr(0x49, IMM, EOR, 2), Instr(0x4A, ACC, LSR, 2), Instr(0x4B, IMM, ALR, 2), Instr(0x4C, ABS, JMP, 3), Instr(0x4D, ABS, EOR, 4), Instr(0x4E, ABS, LSR, 6), Instr(0x4F, ABS, SRE, 6),
+    Instr(0x50, REL, BVC, 2), Instr(0x51, IDY, EOR, 5), Instr(0x52, IMP, XXX, 2), Instr(0x53, IDY, SRE, 8), Instr(0x54, ZPX, NOP, 4), Instr(0x55, ZPX, EOR, 4), Instr(0x56, ZPX, LSR, 6), Instr(0x57, ZPX, SRE, 6), Instr(0x58, IMP, CLI, 2), Instr(0x59, ABY, EOR, 4), Instr(0x5A, IMP, NOP, 2), Instr(0x5B, ABY, SRE, 7), Instr(0x5C, ABX, IGN, 4), Instr(0x5D, ABX, EOR, 4), Instr(0x5E, ABX, LSR, 7), Instr(0x5F, ABX, SRE, 7),
+    Instr(0x60, IMP, RTS, 6), Instr(0x61, IDX, ADC, 6), Instr(0x62, IMP, XXX, 2), Instr(0x63, IDX, RRA, 8), Instr(0x64, ZP0, NOP, 3), Instr(0x65, ZP0, ADC, 3), Instr(0x66, ZP0, ROR, 5), Instr(0x67, ZP0, RRA, 5), Instr(0x68, IMP, PLA, 4), Instr(0x69, IMM, ADC, 2), Instr(0x6A, ACC, ROR, 2), Instr(0x6B, IMM, ARR, 2), Instr(0x6C, IND, JMP, 5), Instr(0x6D, ABS, ADC, 4), Instr(0x6E, ABS, ROR, 6), Instr(0x6F, ABS, RRA, 6),
+    Instr(0x70, REL, BVS, 2), Instr(0x71, IDY, ADC, 5), Instr(0x72, IMP, XXX, 2), Instr(0x73, IDY, RRA, 8), Instr(0x74, ZPX, NOP, 4), Instr(0x75, ZPX, ADC, 4), Instr(0x76, ZPX, ROR, 6), Instr(0x77, ZPX, RRA, 6), Instr(0x78, IMP, SEI, 2), Instr(0x79, ABY, ADC, 4), Instr(0x7A, IMP, NOP, 2), Instr(0x7B, ABY, RRA, 7), Instr(0x7C, ABX, IGN, 4), Instr(0x7D, ABX, ADC, 4), Instr(0x7E, ABX, ROR, 7), Instr(0x7F, ABX, RRA, 7),
+    Instr(0x80, IMM, SKB, 2), Instr(0x81, IDX, STA, 6), Instr(0x82, IMM, SKB, 2), Instr(0x83, IDX, SAX, 6), Instr(0x84, ZP0, STY, 3), Instr(0x85, ZP0, STA, 3), Instr(0x86, ZP0, STX, 3), Instr(0x87, ZP0, SAX, 3), Instr(0x88, IMP, DEY, 2), Instr(0x89, IMM, SKB, 2), Instr(0x8A, IMP, TXA, 2), Instr(0x8B, IMM, XAA, 2), Instr(0x8C, ABS, STY, 4), Instr(0x8D, ABS, STA, 4), Instr(0x8E, ABS, STX, 4), Instr(0x8F, ABS, SAX, 4),
+    Instr(0x90, REL, BCC, 2), Instr(0x91, IDY, STA, 6), Instr(0x92, IMP, XXX, 2), Instr(0x93, IDY, AHX, 6), Instr(0x94, ZPX, STY, 4), Instr(0x95, ZPX, STA, 4), Instr(0x96, ZPY, STX, 4), Instr(0x97, ZPY, SAX, 4), Instr(0x98, IMP, TYA, 2), Instr(0x99, ABY, STA, 5), Instr(0x9A, IMP, TXS, 2), Instr(0x9B, ABY, TAS, 5), Instr(0x9C, ABX, SYA, 5), Instr(0x9D, ABX, STA, 5), Instr(0x9E, ABY, SXA, 5), Instr(0x9F, ABY, AHX, 5),
+    Instr(0xA0, IMM, LDY, 2), Instr(0xA1, IDX, LDA, 6), Instr(0xA2, IMM, LDX, 2), Instr(0xA3, IDX, LAX, 6), Instr(0xA4, ZP0, LDY, 3), Instr(0xA5, ZP0, LDA, 3), Instr(0xA6, ZP0, LDX, 3), Instr(0xA7, ZP0, LAX, 3), Instr(0xA8, IMP, TAY, 2), Instr(0xA9, IMM, LDA, 2), Instr(0xAA, IMP, TAX, 2), Instr(0xAB, IMM, LAX, 2), Instr(0xAC, ABS, LDY, 4), Instr(0xAD, ABS, LDA, 4), Instr(0xAE, ABS, LDX, 4), Instr(0xAF, ABS, LAX, 4),
+    Instr(0xB0, REL, BCS, 2), Instr(0xB1, IDY, LDA, 5), Instr(0xB2, IMP, XXX, 2), Instr(0xB3, IDY, LAX, 5), Instr(0xB4, ZPX, LDY, 4), Instr(0xB5, ZPX, LDA, 4), Instr(0xB6, ZPY, LDX, 4), Instr(0xB7, ZPY, LAX, 4), Instr(0xB8, IMP, CLV, 2), Instr(0xB9, ABY, LDA, 4), Instr(0xBA, IMP, TSX, 2), Instr(0xBB, ABY, LAS, 4), Instr(0xBC, ABX, LDY, 4), Instr(0xBD, ABX, LDA, 4), Instr(0xBE, ABY, LDX, 4), Instr(0xBF, ABY, LAX, 4),
+    Instr(0xC0, IMM, CPY, 2), Instr(0xC1, IDX, CMP, 6), Instr(0xC2, IMM, SKB, 2), Instr(0xC3, IDX, DCP, 8), Instr(0xC4, ZP0, CPY, 3), Instr(0xC5, ZP0, CMP, 3), Instr(0xC6, ZP0, DEC, 5), Instr(0xC7, ZP0, DCP, 5), Instr(0xC8, IMP, INY, 2), Instr(0xC9, IMM, CMP, 2), Instr(0xCA, IMP, DEX, 2), Instr(0xCB, IMM, AXS, 2), Instr(0xCC, ABS, CPY, 4), Instr(0xCD, ABS, CMP, 4), Instr(0xCE, ABS, DEC, 6), Instr(0xCF, ABS, DCP, 6),
+    Instr(0xD0, REL, BNE, 2), Instr(0xD1, IDY, CMP, 5), Instr(0xD2, IMP, XXX, 2), Instr(0xD3, IDY, DCP, 8), Instr(0xD4, ZPX, NOP, 4), Instr(0xD5, ZPX, CMP, 4), Instr(0xD6, ZPX, DEC, 6), Instr(0xD7, ZPX, DCP, 6), Instr(0xD8, IMP, CLD, 2), Instr(0xD9, ABY, CMP, 4), Instr(0xDA, IMP, NOP, 2), Instr(0xDB, ABY, DCP, 7), Instr(0xDC, ABX, IGN, 4), Instr(0xDD, ABX, CMP, 4), Instr(0xDE, ABX, DEC, 7), Instr(0xDF, ABX, DCP, 7),
+    Instr(0xE0, IMM, CPX, 2), Instr(0xE1, IDX, SBC, 6), Instr(0xE2, IMM, SKB, 2), Instr(0xE3, IDX, ISB, 8), Instr(0xE4, ZP0, CPX, 3), Instr(0xE5, ZP0, SBC, 3), Instr(0xE6, ZP0, INC, 5), Instr(0xE7, ZP0, ISB, 5), Instr(0xE8, IMP, INX, 2), Instr(0xE9, IMM, SBC, 2), Instr(0xEA, IMP, NOP, 2), Instr(0xEB, IMM, SBC, 2), Instr(0xEC, ABS, CPX, 4), Instr(0xED, ABS, SBC, 4), Instr(0xEE, ABS, INC, 6), Instr(0xEF, ABS, ISB, 6),
+    Instr(0xF0, REL, BEQ, 2), Instr(0xF1, IDY, SBC, 5), Instr(0xF2, IMP, XXX, 2), Instr(0xF3, IDY, ISB, 8), Instr(0xF4, ZPX, NOP, 4), Instr(0xF5, ZPX, SBC, 4), Instr(0xF6, ZPX, INC, 6), Instr(0xF7, ZPX, ISB, 6), Instr(0xF8, IMP, SED, 2), Instr(0xF9, ABY, SBC, 4), Instr(0xFA, IMP, NOP, 2), Instr(0xFB, ABY, ISB, 7), Instr(0xFC, ABX, IGN, 4), Instr(0xFD, ABX, SBC, 4), Instr(0xFE, ABX, INC, 7), Instr(0xFF, ABX, ISB, 7),
+];
+
+// W65C02S with the Rockwell bit instructions; undefined opcodes are NOPs
+pub const INSTRUCTIONS_65C02: [Instr; 256] = [
+    Instr(0x00, IMM, BRK, 7), Instr(0x01, IDX, ORA, 6), Instr(0x02, IMM, UND, 2), Instr(0x03, NUL, UND, 1), Instr(0x04, ZP0, TSB, 5), Instr(0x05, ZP0, ORA, 3), Instr(0x06, ZP0, ASL, 5), Instr(0x07, ZP0, RMB, 5), Instr(0x08, IMP, PHP, 3), Instr(0x09, IMM, ORA, 2), Instr(0x0A, ACC, ASL, 2), Instr(0x0B, NUL, UND, 1), Instr(0x0C, ABS, TSB, 6), Instr(0x0D, ABS, ORA, 4), Instr(0x0E, ABS, ASL, 6), Instr(0x0F, ZPR, BBR, 5),
+    Instr(0x10, REL, BPL, 2), Instr(0x11, IDY, ORA, 5), Instr(0x12, ZPI, ORA, 5), Instr(0x13, NUL, UND, 1), Instr(0x14, ZP0, TRB, 5), Instr(0x15, ZPX, ORA, 4), Instr(0x16, ZPX, ASL, 6), Instr(0x17, ZP0, RMB, 5), Instr(0x18, IMP, CLC, 2), Instr(0x19, ABY, ORA, 4), Instr(0x1A, ACC, INC, 2), Instr(0x1B, NUL, UND, 1), Instr(0x1C, ABS, TRB, 6), Instr(0x1D, ABX, ORA, 4), Instr(0x1E, ABX, ASL, 7), Instr(0x1F, ZPR, BBR, 5),
+    Instr(0x20, ABS, JSR, 6), Instr(0x21, IDX, AND, 6), Instr(0x22, IMM, UND, 2), Instr(0x23, NUL, UND, 1), Instr(0x24, ZP0, BIT, 3), Instr(0x25, ZP0, AND, 3), Instr(0x26, ZP0, ROL, 5), Instr(0x27, ZP0, RMB, 5), Instr(0x28, IMP, PLP, 4), Instr(0x29, IMM, AND, 2), Instr(0x2A, ACC, ROL, 2), Instr(0x2B, NUL, UND, 1), Instr(0x2C, ABS, BIT, 4), Instr(0x2D, ABS, AND, 4), Instr(0x2E, ABS, ROL, 6), Instr(0x2F, ZPR, BBR, 5),
+    Instr(0x30, REL, BMI, 2), Instr(0x31, IDY, AND, 5), Instr(0x32, ZPI, AND, 5), Instr(0x33, NUL, UND, 1), Instr(0x34, ZPX, BIT, 4), Instr(0x35, ZPX, AND, 4), Instr(0x36, ZPX, ROL, 6), Instr(0x37, ZP0, RMB, 5), Instr(0x38, IMP, SEC, 2), Instr(0x39, ABY, AND, 4), Instr(0x3A, ACC, DEC, 2), Instr(0x3B, NUL, UND, 1), Instr(0x3C, ABX, BIT, 4), Instr(0x3D, ABX, AND, 4), Instr(0x3E, ABX, ROL, 7), Instr(0x3F, ZPR, BBR, 5),
+    Instr(0x40, IMP, RTI, 6), Instr(0x41, IDX, EOR, 6), Instr(0x42, IMM, UND, 2), Instr(0x43, NUL, UND, 1), Instr(0x44, ZP0, UND, 3), Instr(0x45, ZP0, EOR, 3), Instr(0x46, ZP0, LSR, 5), Instr(0x47, ZP0, RMB, 5), Instr(0x48, IMP, PHA, 3), Instr(0x49, IMM, EOR, 2), Instr(0x4A, ACC, LSR, 2), Instr(0x4B, NUL, UND, 1), Instr(0x4C, ABS, JMP, 3), Instr(0x4D, ABS, EOR, 4), Instr(0x4E, ABS, LSR, 6), Instr(0x4F, ZPR, BBR, 5),
+    Instr(0x50, REL, BVC, 2), Instr(0x51, IDY, EOR, 5), Instr(0x52, ZPI, EOR, 5), Instr(0x53, NUL, UND, 1), Instr(0x54, ZPX, UND, 4), Instr(0x55, ZPX, EOR, 4), Instr(0x56, ZPX, LSR, 6), Instr(0x57, ZP0, RMB, 5), Instr(0x58, IMP, CLI, 2), Instr(0x59, ABY, EOR, 4), Instr(0x5A, IMP, PHY, 3), Instr(0x5B, NUL, UND, 1), Instr(0x5C, ABS, UND, 8), Instr(0x5D, ABX, EOR, 4), Instr(0x5E, ABX, LSR, 7), Instr(0x5F, ZPR, BBR, 5),
+    Instr(0x60, IMP, RTS, 6), Instr(0x61, IDX, ADC, 6), Instr(0x62, IMM, UND, 2), Instr(0x63, NUL, UND, 1), Instr(0x64, ZP0, STZ, 3), Instr(0x65, ZP0, ADC, 3), Instr(0x66, ZP0, ROR, 5), Instr(0x67, ZP0, RMB, 5), Instr(0x68, IMP, PLA, 4), Instr(0x69, IMM, ADC, 2), Instr(0x6A, ACC, ROR, 2), Instr(0x6B, NUL, UND, 1), Instr(0x6C, IND, JMP, 6), Instr(0x6D, ABS, ADC, 4), Instr(0x6E, ABS, ROR, 6), Instr(0x6F, ZPR, BBR, 5),
+    Instr(0x70, REL, BVS, 2), Instr(0x71, IDY, ADC, 5), Instr(0x72, ZPI, ADC, 5), Instr(0x73, NUL, UND, 1), Instr(0x74, ZPX, STZ, 4), Instr(0x75, ZPX, ADC, 4), Instr(0x76, ZPX, ROR, 6), Instr(0x77, ZP0, RMB, 5), Instr(0x78, IMP, SEI, 2), Instr(0x79, ABY, ADC, 4), Instr(0x7A, IMP, PLY, 4), Instr(0x7B, NUL, UND, 1), Instr(0x7C, IAX, JMP, 6), Instr(0x7D, ABX, ADC, 4), Instr(0x7E, ABX, ROR, 7), Instr(0x7F, ZPR, BBR, 5),
+    Instr(0x80, REL, BRA, 3), Instr(0x81, IDX, STA, 6), Instr(0x82, IMM, UND, 2), Instr(0x83, NUL, UND, 1), Instr(0x84, ZP0, STY, 3), Instr(0x85, ZP0, STA, 3), Instr(0x86, ZP0, STX, 3), Instr(0x87, ZP0, SMB, 5), Instr(0x88, IMP, DEY, 2), Instr(0x89, IMM, BIT, 2), Instr(0x8A, IMP, TXA, 2), Instr(0x8B, NUL, UND, 1), Instr(0x8C, ABS, STY, 4), Instr(0x8D, ABS, STA, 4), Instr(0x8E, ABS, STX, 4), Instr(0x8F, ZPR, BBS, 5),
+    Instr(0x90, REL, BCC, 2), Instr(0x91, IDY, STA, 6), Instr(0x92, ZPI, STA, 5), Instr(0x93, NUL, UND, 1), Instr(0x94, ZPX, STY, 4), Instr(0x95, ZPX, STA, 4), Instr(0x96, ZPY, STX, 4), Instr(0x97, ZP0, SMB, 5), Instr(0x98, IMP, TYA, 2), Instr(0x99, ABY, STA, 5), Instr(0x9A, IMP, TXS, 2), Instr(0x9B, NUL, UND, 1), Instr(0x9C, ABS, STZ, 4), Instr(0x9D, ABX, STA, 5), Instr(0x9E, ABX, STZ, 5), Instr(0x9F, ZPR, BBS, 5),
+    Instr(0xA0, IMM, LDY, 2), Instr(0xA1, IDX, LDA, 6), Instr(0xA2, IMM, LDX, 2), Instr(0xA3, NUL, UND, 1), Instr(0xA4, ZP0, LDY, 3), Instr(0xA5, ZP0, LDA, 3), Instr(0xA6, ZP0, LDX, 3), Instr(0xA7, ZP0, SMB, 5), Instr(0xA8, IMP, TAY, 2), Instr(0xA9, IMM, LDA, 2), Instr(0xAA, IMP, TAX, 2), Instr(0xAB, NUL, UND, 1), Instr(0xAC, ABS, LDY, 4), Instr(0xAD, ABS, LDA, 4), Instr(0xAE, ABS, LDX, 4), Instr(0xAF, ZPR, BBS, 5),
+    Instr(0xB0, REL, BCS, 2), Instr(0xB1, IDY, LDA, 5), Instr(0xB2, ZPI, LDA, 5), Instr(0xB3, NUL, UND, 1), Instr(0xB4, ZPX, LDY, 4), Instr(0xB5, ZPX, LDA, 4), Instr(0xB6, ZPY, LDX, 4), Instr(0xB7, ZP0, SMB, 5), Instr(0xB8, IMP, CLV, 2), Instr(0xB9, ABY, LDA, 4), Instr(0xBA, IMP, TSX, 2), Instr(0xBB, NUL, UND, 1), Instr(0xBC, ABX, LDY, 4), Instr(0xBD, ABX, LDA, 4), Instr(0xBE, ABY, LDX, 4), Instr(0xBF, ZPR, BBS, 5),
+    Instr(0xC0, IMM, CPY, 2), Instr(0xC1, IDX, CMP, 6), Instr(0xC2, IMM, UND, 2), Instr(0xC3, NUL, UND, 1), Instr(0xC4, ZP0, CPY, 3), Instr(0xC5, ZP0, CMP, 3), Instr(0xC6, ZP0, DEC, 5), Instr(0xC7, ZP0, SMB, 5), Instr(0xC8, IMP, INY, 2), Instr(0xC9, IMM, CMP, 2), Instr(0xCA, IMP, DEX, 2), Instr(0xCB, IMP, WAI, 3), Instr(0xCC, ABS, CPY, 4), Instr(0xCD, ABS, CMP, 4), Instr(0xCE, ABS, DEC, 6), Instr(0xCF, ZPR, BBS, 5),
+    Instr(0xD0, REL, BNE, 2), Instr(0xD1, IDY, CMP, 5), Instr(0xD2, ZPI, CMP, 5), Instr(0xD3, NUL, UND, 1), Instr(0xD4, ZPX, UND, 4), Instr(0xD5, ZPX, CMP, 4), Instr(0xD6, ZPX, DEC, 6), Instr(0xD7, ZP0, SMB, 5), Instr(0xD8, IMP, CLD, 2), Instr(0xD9, ABY, CMP, 4), Instr(0xDA, IMP, PHX, 3), Instr(0xDB, IMP, STP, 3), Instr(0xDC, ABS, UND, 4), Instr(0xDD, ABX, CMP, 4), Instr(0xDE, ABX, DEC, 7), Instr(0xDF, ZPR, BBS, 5),
+    Instr(0xE0, IMM, CPX, 2), Instr(0xE1, IDX, SBC, 6), Instr(0xE2, IMM, UND, 2), Instr(0xE3, NUL, UND, 1), Instr(0xE4, ZP0, CPX, 3), Instr(0xE5, ZP0, SBC, 3), Instr(0xE6, ZP0, INC, 5), Instr(0xE7, ZP0, SMB, 5), Instr(0xE8, IMP, INX, 2), Instr(0xE9, IMM, SBC, 2), Instr(0xEA, IMP, NOP, 2), Instr(0xEB, NUL, UND, 1), Instr(0xEC, ABS, CPX, 4), Instr(0xED, ABS, SBC, 4), Instr(0xEE, ABS, INC, 6), Instr(0xEF, ZPR, BBS, 5),
+    Instr(0xF0, REL, BEQ, 2), Instr(0xF1, IDY, SBC, 5), Instr(0xF2, ZPI, SBC, 5), Instr(0xF3, NUL, UND, 1), Instr(0xF4, ZPX, UND, 4), Instr(0xF5, ZPX, SBC, 4), Instr(0xF6, ZPX, INC, 6), Instr(0xF7, ZP0, SMB, 5), Instr(0xF8, IMP, SED, 2), Instr(0xF9, ABY, SBC, 4), Instr(0xFA, IMP, PLX, 4), Instr(0xFB, NUL, UND, 1), Instr(0xFC, ABS, UND, 4), Instr(0xFD, ABX, SBC, 4), Instr(0xFE, ABX, INC, 7), Instr(0xFF, ZPR, BBS, 5),
+];
+
+impl<B: CpuBus> CPU<B> {
+    // the unstable XAA ORs A with a chip-dependent constant before the AND, $EE is the
+    // most commonly observed value
+    const XAA_MAGIC: u8 = 0xEE;
 
     //
     // addressing modes
@@ -116,7 +146,11 @@ impl CPU {
 
     pub fn ind(&mut self) {
         let addr = self.read_instr_u16();
-        if addr & 0xFF == 0xFF {
+        if self.variant == Variant::WDC65C02 {
+            // fixed on the 65C02, at the cost of a cycle
+            let _ = self.read(self.pc.wrapping_sub(1));
+            self.abs_addr = self.read_u16(addr);
+        } else if addr & 0xFF == 0xFF {
             // buggy indirect
             let lo = self.read(addr);
             let hi = self.read(addr & 0xFF00);
@@ -150,6 +184,28 @@ impl CPU {
         self.abs_addr = addr.wrapping_add(self.y.into()) & 0x00FF;
     }
 
+    pub fn zpi(&mut self) {
+        let addr = self.read_instr();
+        self.abs_addr = self.read_zp_u16(addr);
+    }
+
+    pub fn iax(&mut self) {
+        let addr = self.read_instr_u16();
+        let _ = self.read(self.pc.wrapping_sub(1));
+        self.abs_addr = self.read_u16(addr.wrapping_add(self.x.into()));
+    }
+
+    // BBR/BBS: zero page operand to test, then the branch offset
+    pub fn zpr(&mut self) {
+        self.abs_addr = u16::from(self.read_instr());
+        self.fetched_data = self.read(self.abs_addr);
+        let _ = self.read(self.abs_addr);
+        self.rel_addr = u16::from(self.read_instr());
+    }
+
+    // single byte, single cycle: not even a dummy read
+    pub fn nul(&mut self) {}
+
     //
     // operations
     //
@@ -216,8 +272,11 @@ impl CPU {
         self.fetch_data_cross();
         let val = self.a & self.fetched_data;
         self.status.set(Status::Z, val == 0);
-        self.status.set(Status::N, self.fetched_data & (1 << 7) > 0);
-        self.status.set(Status::V, self.fetched_data & (1 << 6) > 0);
+        // BIT #imm only sets Z
+        if self.instr.addr_mode() != IMM {
+            self.status.set(Status::N, self.fetched_data & (1 << 7) > 0);
+            self.status.set(Status::V, self.fetched_data & (1 << 6) > 0);
+        }
     }
 
     pub fn bcc(&mut self) {
@@ -282,21 +341,12 @@ impl CPU {
 
     pub fn adc(&mut self) {
         self.fetch_data_cross();
-        let a = self.a;
-        let (x1, o1) = self.fetched_data.overflowing_add(a);
-        let (x2, o2) = x1.overflowing_add(self.status_bit(Status::C));
-        self.a = x2;
-        self.status.set(Status::C, o1 | o2);
-        self.status.set(
-            Status::V,
-            (a ^ self.fetched_data) & 0x80 == 0 && (a ^ self.a) & 0x80 != 0,
-        );
-        self.set_zn_status(self.a);
+        self.add_with_carry(self.fetched_data);
     }
 
     pub fn lsr(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         self.status.set(Status::C, self.fetched_data & 1 > 0);
         let val = self.fetched_data.wrapping_shr(1);
         self.set_zn_status(val);
@@ -305,7 +355,7 @@ impl CPU {
 
     pub fn asl(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         self.status.set(Status::C, (self.fetched_data >> 7) & 1 > 0);
         let val = self.fetched_data.wrapping_shl(1);
         self.set_zn_status(val);
@@ -314,7 +364,7 @@ impl CPU {
 
     pub fn ror(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let mut ret = self.fetched_data.rotate_right(1);
         if self.status.intersects(Status::C) {
             ret |= 1 << 7;
@@ -328,7 +378,7 @@ impl CPU {
 
     pub fn rol(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let old_c = self.status_bit(Status::C);
         self.status.set(Status::C, (self.fetched_data >> 7) & 1 > 0);
         let val = (self.fetched_data << 1) | old_c;
@@ -383,26 +433,20 @@ impl CPU {
     }
 
     pub fn rts(&mut self) {
-        self.pc = self.pop_u16().wrapping_add(1);
+        let _ = self.read(Self::SP_BASE | u16::from(self.sp));
+        self.pc = self.pop_u16();
+        let _ = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
     }
 
     pub fn sbc(&mut self) {
         self.fetch_data_cross();
-        let a = self.a;
-        let (x1, o1) = a.overflowing_sub(self.fetched_data);
-        let (x2, o2) = x1.overflowing_sub(1 - self.status_bit(Status::C));
-        self.a = x2;
-        self.status.set(Status::C, !(o1 | o2));
-        self.status.set(
-            Status::V,
-            (a ^ self.fetched_data) & 0x80 != 0 && (a ^ self.a) & 0x80 != 0,
-        );
-        self.set_zn_status(self.a);
+        self.sub_with_carry(self.fetched_data);
     }
 
     pub fn inc(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let val = self.fetched_data.wrapping_add(1);
         self.set_zn_status(val);
         self.write_fetched(val);
@@ -410,7 +454,7 @@ impl CPU {
 
     pub fn dec(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let val = self.fetched_data.wrapping_sub(1);
         self.set_zn_status(val);
         self.write_fetched(val);
@@ -500,7 +544,7 @@ impl CPU {
 
     pub fn dcp(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let val = self.fetched_data.wrapping_sub(1);
         self.compare(self.a, val);
         self.write_fetched(val);
@@ -508,24 +552,15 @@ impl CPU {
 
     pub fn isb(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let val = self.fetched_data.wrapping_add(1);
-        let a = self.a;
-        let (x1, o1) = a.overflowing_sub(val);
-        let (x2, o2) = x1.overflowing_sub(1 - self.status_bit(Status::C));
-        self.a = x2;
-        self.status.set(Status::C, !(o1 | o2));
-        self.status.set(
-            Status::V,
-            (a ^ val) & 0x80 != 0 && (a ^ self.a) & 0x80 != 0,
-        );
-        self.set_zn_status(self.a);
+        self.sub_with_carry(val);
         self.write_fetched(val);
     }
 
     pub fn slo(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         self.status.set(Status::C, (self.fetched_data >> 7) & 1 > 0);
         let val = self.fetched_data.wrapping_shl(1);
         self.write_fetched(val);
@@ -535,7 +570,7 @@ impl CPU {
 
     pub fn rla(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let old_c = self.status_bit(Status::C);
         self.status.set(Status::C, (self.fetched_data >> 7) & 1 > 0);
         let val = (self.fetched_data << 1) | old_c;
@@ -546,7 +581,7 @@ impl CPU {
 
     pub fn sre(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         self.status.set(Status::C, self.fetched_data & 1 > 0);
         let val = self.fetched_data.wrapping_shr(1);
         self.a ^= val;
@@ -556,7 +591,7 @@ impl CPU {
 
     pub fn rra(&mut self) {
         self.fetch_data();
-        self.write_fetched(self.fetched_data);
+        self.rmw_dummy_access();
         let mut ret = self.fetched_data.rotate_right(1);
         if self.status.intersects(Status::C) {
             ret |= 1 << 7;
@@ -564,44 +599,98 @@ impl CPU {
             ret &= !(1 << 7);
         }
         self.status.set(Status::C, self.fetched_data & 1 > 0);
-        let a = self.a;
-        let (x1, o1) = ret.overflowing_add(a);
-        let (x2, o2) = x1.overflowing_add(self.status_bit(Status::C));
-        self.a = x2;
-        self.status.set(Status::C, o1 | o2);
-        self.status.set(
-            Status::V,
-            (a ^ ret) & 0x80 == 0 && (a ^ self.a) & 0x80 != 0,
-        );
-        self.set_zn_status(self.a);
+        self.add_with_carry(ret);
         self.write_fetched(ret);
     }
 
     pub fn brk(&mut self) {
-        self.pc += 1;
-
-        self.status.set(Status::I, true);
+        // the padding byte after BRK is read and skipped
+        let _ = self.read(self.abs_addr);
 
         self.push_u16(self.pc);
 
-        self.status.set(Status::B, true);
-        self.push(self.status.bits());
-        self.status.set(Status::B, false);
+        self.push((self.status | Status::U | Status::B).bits());
+        self.status.set(Status::I, true);
+        self.clear_decimal_on_interrupt();
 
         self.pc = self.read_u16(0xFFFE);
     }
 
     pub fn tas(&mut self) {
-        self.write(self.abs_addr, self.a);
-        self.sp = self.x;
+        self.sp = self.a & self.x;
+        self.unstable_store(self.a & self.x);
     }
 
     pub fn sxa(&mut self) {
-        let hi = (self.abs_addr >> 8) as u8;
-        let lo = (self.abs_addr & 0xFF) as u8;
-        let val = self.x & hi.wrapping_add(1);
-        self.abs_addr = u16::from_le_bytes([lo, self.x & hi.wrapping_add(1)]);
-        self.write_fetched(val);
+        self.unstable_store(self.x);
+    }
+
+    pub fn anc(&mut self) {
+        self.and();
+        self.status.set(Status::C, self.a & 0x80 == 0x80);
+    }
+
+    pub fn alr(&mut self) {
+        self.fetch_data();
+        let val = self.a & self.fetched_data;
+        self.status.set(Status::C, val & 1 > 0);
+        self.a = val >> 1;
+        self.set_zn_status(self.a);
+    }
+
+    pub fn arr(&mut self) {
+        self.fetch_data();
+        let val = self.a & self.fetched_data;
+        self.a = (val >> 1) | (self.status_bit(Status::C) << 7);
+        self.set_zn_status(self.a);
+
+        if self.decimal_mode() {
+            // NMOS decimal ARR fixes up each nibble of the rotated value
+            self.status.set(Status::V, (val ^ self.a) & 0x40 > 0);
+            let (hi, lo) = (val >> 4, val & 0x0F);
+            if lo + (lo & 0x01) > 5 {
+                self.a = (self.a & 0xF0) | (self.a.wrapping_add(6) & 0x0F);
+            }
+            let carry = hi + (hi & 0x01) > 5;
+            if carry {
+                self.a = self.a.wrapping_add(0x60);
+            }
+            self.status.set(Status::C, carry);
+        } else {
+            self.status.set(Status::C, self.a & 0x40 > 0);
+            self.status.set(Status::V, ((self.a >> 6) ^ (self.a >> 5)) & 1 > 0);
+        }
+    }
+
+    pub fn axs(&mut self) {
+        self.fetch_data();
+        let val = self.a & self.x;
+        self.x = val.wrapping_sub(self.fetched_data);
+        self.status.set(Status::C, val >= self.fetched_data);
+        self.set_zn_status(self.x);
+    }
+
+    pub fn las(&mut self) {
+        self.fetch_data_cross();
+        let val = self.fetched_data & self.sp;
+        self.a = val;
+        self.x = val;
+        self.sp = val;
+        self.set_zn_status(val);
+    }
+
+    pub fn xaa(&mut self) {
+        self.fetch_data();
+        self.a = (self.a | Self::XAA_MAGIC) & self.x & self.fetched_data;
+        self.set_zn_status(self.a);
+    }
+
+    pub fn ahx(&mut self) {
+        self.unstable_store(self.a & self.x);
+    }
+
+    pub fn sya(&mut self) {
+        self.unstable_store(self.y);
     }
 
     pub fn cli(&mut self) {
@@ -609,8 +698,149 @@ impl CPU {
     }
 
     pub fn xxx(&mut self) {
-        self.corrupted = true;
-        panic!("corrupted");
+        // park on the JAM opcode so debuggers show where we died
+        self.pc = self.pc.wrapping_sub(1);
+        self.state = State::Halted;
+        self.bus.report(Diagnostic::Jam {
+            pc: self.pc,
+            opcode: self.instr.opcode(),
+        });
+    }
+
+    pub fn bra(&mut self) {
+        self.branch();
+    }
+
+    pub fn phx(&mut self) {
+        self.push(self.x);
+    }
+
+    pub fn phy(&mut self) {
+        self.push(self.y);
+    }
+
+    pub fn plx(&mut self) {
+        let _ = self.read(Self::SP_BASE | u16::from(self.sp));
+        self.x = self.pop();
+        self.set_zn_status(self.x);
+    }
+
+    pub fn ply(&mut self) {
+        let _ = self.read(Self::SP_BASE | u16::from(self.sp));
+        self.y = self.pop();
+        self.set_zn_status(self.y);
+    }
+
+    pub fn stz(&mut self) {
+        self.write(self.abs_addr, 0);
+    }
+
+    pub fn tsb(&mut self) {
+        self.fetch_data();
+        self.rmw_dummy_access();
+        self.status.set(Status::Z, self.a & self.fetched_data == 0);
+        self.write_fetched(self.fetched_data | self.a);
+    }
+
+    pub fn trb(&mut self) {
+        self.fetch_data();
+        self.rmw_dummy_access();
+        self.status.set(Status::Z, self.a & self.fetched_data == 0);
+        self.write_fetched(self.fetched_data & !self.a);
+    }
+
+    pub fn wai(&mut self) {
+        let _ = self.read(self.pc);
+        self.state = State::Waiting;
+    }
+
+    pub fn stp(&mut self) {
+        let _ = self.read(self.pc);
+        self.state = State::Halted;
+    }
+
+    pub fn bbr(&mut self) {
+        if self.fetched_data & self.opcode_bit() == 0 {
+            self.branch();
+        }
+    }
+
+    pub fn bbs(&mut self) {
+        if self.fetched_data & self.opcode_bit() != 0 {
+            self.branch();
+        }
+    }
+
+    pub fn rmb(&mut self) {
+        self.fetch_data();
+        self.rmw_dummy_access();
+        self.write_fetched(self.fetched_data & !self.opcode_bit());
+    }
+
+    pub fn smb(&mut self) {
+        self.fetch_data();
+        self.rmw_dummy_access();
+        self.write_fetched(self.fetched_data | self.opcode_bit());
+    }
+
+    // undefined 65C02 opcodes: the addressing mode consumed the operand bytes, idle away
+    // whatever cycles are left
+    pub fn und(&mut self) {
+        let used = match self.instr.addr_mode() {
+            NUL | IMM => 1,
+            ZP0 => 2,
+            _ => 3,
+        };
+        for _ in used..self.instr.cycles() {
+            let _ = self.read(self.abs_addr);
+        }
+    }
+
+    // the bit number of RMB/SMB/BBR/BBS lives in the opcode's high nibble
+    fn opcode_bit(&self) -> u8 {
+        1 << ((self.instr.opcode() >> 4) & 0x07)
+    }
+
+    // NMOS parts write the unmodified value back before the result, the 65C02 reads it again
+    fn rmw_dummy_access(&mut self) {
+        if self.variant == Variant::WDC65C02 && !matches!(self.instr.addr_mode(), IMP | ACC) {
+            let _ = self.read(self.abs_addr);
+        } else {
+            self.write_fetched(self.fetched_data);
+        }
+    }
+
+    pub(crate) fn clear_decimal_on_interrupt(&mut self) {
+        if self.variant == Variant::WDC65C02 {
+            self.status.set(Status::D, false);
+        }
+    }
+
+    // SHA/SHX/SHY/SHS: the value is ANDed with the operand high byte + 1, and when indexing
+    // crossed a page that value also replaces the high byte of the target address. If DMA
+    // stalled the cycle before the write, the AND with H+1 drops out.
+    fn unstable_store(&mut self, val: u8) {
+        let hi = self.base_addr_hi();
+        let [lo, target_hi] = self.abs_addr.to_le_bytes();
+
+        let val = if self.dma_halted { val } else { val & hi.wrapping_add(1) };
+        let addr = if hi != target_hi {
+            u16::from_le_bytes([lo, val])
+        } else {
+            self.abs_addr
+        };
+
+        self.write(addr, val);
+    }
+
+    // high byte of the operand address before indexing
+    fn base_addr_hi(&self) -> u8 {
+        let index = match self.instr.addr_mode() {
+            ABX => self.x,
+            ABY | IDY => self.y,
+            _ => 0,
+        };
+        (self.abs_addr.wrapping_sub(u16::from(index)) >> 8) as u8
     }
 
     fn branch(&mut self) {
@@ -629,6 +859,89 @@ impl CPU {
         self.pc = self.abs_addr;
     }
 
+    fn decimal_mode(&self) -> bool {
+        self.status.contains(Status::D) && self.variant.has_decimal_mode()
+    }
+
+    fn add_with_carry(&mut self, val: u8) {
+        let a = self.a;
+        let carry = self.status_bit(Status::C);
+        let (x1, o1) = val.overflowing_add(a);
+        let (x2, o2) = x1.overflowing_add(carry);
+
+        if !self.decimal_mode() {
+            self.a = x2;
+            self.status.set(Status::C, o1 | o2);
+            self.status.set(Status::V, (a ^ val) & 0x80 == 0 && (a ^ self.a) & 0x80 != 0);
+            self.set_zn_status(self.a);
+            return;
+        }
+
+        // NMOS decimal: Z comes from the binary sum, N and V from the sum after the low
+        // nibble fixup but before the high one
+        let mut lo = i16::from(a & 0x0F) + i16::from(val & 0x0F) + i16::from(carry);
+        if lo >= 0x0A {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
+        }
+
+        let signed = i16::from((a & 0xF0) as i8) + i16::from((val & 0xF0) as i8) + lo;
+        self.status.set(Status::Z, x2 == 0);
+        self.status.set(Status::N, signed & 0x80 != 0);
+        self.status.set(Status::V, !(-128..=127).contains(&signed));
+
+        let mut sum = i16::from(a & 0xF0) + i16::from(val & 0xF0) + lo;
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.status.set(Status::C, sum >= 0x100);
+        self.a = sum as u8;
+
+        if self.variant == Variant::WDC65C02 {
+            // the 65C02 takes an extra cycle to make N and Z valid
+            let _ = self.read(self.pc);
+            self.set_zn_status(self.a);
+        }
+    }
+
+    fn sub_with_carry(&mut self, val: u8) {
+        let a = self.a;
+        let borrow = 1 - self.status_bit(Status::C);
+        let (x1, o1) = a.overflowing_sub(val);
+        let (x2, o2) = x1.overflowing_sub(borrow);
+
+        // NMOS flags always come from the binary difference
+        self.a = x2;
+        self.status.set(Status::C, !(o1 | o2));
+        self.status.set(Status::V, (a ^ val) & 0x80 != 0 && (a ^ self.a) & 0x80 != 0);
+        self.set_zn_status(self.a);
+
+        if self.decimal_mode() && self.variant == Variant::WDC65C02 {
+            let lo = i16::from(a & 0x0F) - i16::from(val & 0x0F) - i16::from(borrow);
+            let mut diff = i16::from(a) - i16::from(val) - i16::from(borrow);
+            if diff < 0 {
+                diff -= 0x60;
+            }
+            if lo < 0 {
+                diff -= 0x06;
+            }
+            self.a = diff as u8;
+
+            let _ = self.read(self.pc);
+            self.set_zn_status(self.a);
+        } else if self.decimal_mode() {
+            let mut lo = i16::from(a & 0x0F) - i16::from(val & 0x0F) - i16::from(borrow);
+            if lo < 0 {
+                lo = ((lo - 0x06) & 0x0F) - 0x10;
+            }
+
+            let mut diff = i16::from(a & 0xF0) - i16::from(val & 0xF0) + lo;
+            if diff < 0 {
+                diff -= 0x60;
+            }
+            self.a = diff as u8;
+        }
+    }
+
     fn compare(&mut self, a: u8, b: u8) {
         let result = a.wrapping_sub(b);
         self.set_zn_status(result);
@@ -0,0 +1,23 @@
+use std::fmt;
+use std::sync::Arc;
+
+// Things the guest program did that real hardware tolerates but that usually
+// point at an emulation gap or a buggy ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    Jam { pc: u16, opcode: u8 },
+    UnmappedRead(u16),
+    UnmappedWrite(u16, u8),
+}
+
+pub type DiagnosticHandler = Arc<dyn Fn(Diagnostic) + Send + Sync>;
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Jam { pc, opcode } => write!(f, "CPU halted by JAM opcode {opcode:02X} at {pc:04X}"),
+            Diagnostic::UnmappedRead(addr) => write!(f, "read from unmapped address {addr:04X}"),
+            Diagnostic::UnmappedWrite(addr, data) => write!(f, "write of {data:02X} to unmapped address {addr:04X}"),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::any::Any;
+use std::str::FromStr;
+
+use crate::ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Whatever is plugged into a controller port. Both ports see the $4016 strobe line, and a
+// read from $4016/$4017 puts the device's answer on D0-D4; the other bits are open bus.
+pub trait InputDevice: Any + Send + CloneDevice {
+    fn strobe(&mut self, on: bool);
+
+    // only D0-D4 are wired to the port; a peek mustn't move the device along. The PPU is
+    // caught up to the read, for devices that watch the screen.
+    fn read(&mut self, ppu: &PPU, read_only: bool) -> u8;
+
+    // the standard pads behind this port: slot 0 is the one on the port itself, slot 1 the
+    // extra one an adapter adds
+    fn pad_mut(&mut self, _slot: usize) -> Option<&mut Controller> {
+        None
+    }
+}
+
+// what's plugged into the ports, from the NES 2.0 header or picked by hand
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    #[default]
+    STANDARD,
+    // NES Four Score: pads 3 and 4 come after pads 1 and 2 in each port's serial stream
+    FOURSCORE,
+    // Famicom 4-player adapter on the expansion port: pads 3 and 4 on D1
+    FAMICOM4P,
+    // a pad in port 1 and a Zapper in port 2
+    ZAPPER,
+}
+
+impl InputType {
+    // NES 2.0 byte 15, the default expansion device
+    pub fn from_nes2(device: u8) -> InputType {
+        match device {
+            0x02 => InputType::FOURSCORE,
+            0x03 => InputType::FAMICOM4P,
+            0x08 => InputType::ZAPPER,
+            _ => InputType::STANDARD,
+        }
+    }
+
+    // a fresh device for each port
+    pub fn devices(&self) -> [Box<dyn InputDevice>; 2] {
+        match self {
+            InputType::STANDARD => [Box::new(Controller::new()), Box::new(Controller::new())],
+            InputType::FOURSCORE => [Box::new(FourScore::new(0)), Box::new(FourScore::new(1))],
+            InputType::FAMICOM4P => [Box::new(Famicom4P::new()), Box::new(Famicom4P::new())],
+            InputType::ZAPPER => [Box::new(Controller::new()), Box::new(Zapper::new())],
+        }
+    }
+}
+
+impl FromStr for InputType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "standard" => Ok(InputType::STANDARD),
+            "fourscore" => Ok(InputType::FOURSCORE),
+            "famicom4p" => Ok(InputType::FAMICOM4P),
+            "zapper" => Ok(InputType::ZAPPER),
+            x => Err(format!("unknown input {x}")),
+        }
+    }
+}
+
+// lets a Box<dyn InputDevice> be cloned along with the rest of the machine
+pub trait CloneDevice {
+    fn clone_device(&self) -> Box<dyn InputDevice>;
+}
+
+impl<T: InputDevice + Clone> CloneDevice for T {
+    fn clone_device(&self) -> Box<dyn InputDevice> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn InputDevice> {
+    fn clone(&self) -> Self {
+        self.clone_device()
+    }
+}
+
+// The standard pad: a shift register of the eight buttons on D0, A first. It keeps
+// reloading while the strobe is high and latches when it drops; once all eight buttons
+// are out it reads 1.
+#[derive(Debug, Default, Clone)]
+pub struct Controller {
+    pub buttons: u8,
+    shift: u8,
+    strobe: bool,
+}
+
+impl Controller {
+    pub const A: u8 = 0x80;
+    pub const B: u8 = 0x40;
+    pub const SELECT: u8 = 0x20;
+    pub const START: u8 = 0x10;
+    pub const UP: u8 = 0x08;
+    pub const DOWN: u8 = 0x04;
+    pub const LEFT: u8 = 0x02;
+    pub const RIGHT: u8 = 0x01;
+
+    pub fn new() -> Controller {
+        Controller::default()
+    }
+}
+
+impl InputDevice for Controller {
+    fn strobe(&mut self, on: bool) {
+        if self.strobe || on {
+            self.shift = self.buttons;
+        }
+        self.strobe = on;
+    }
+
+    fn read(&mut self, _ppu: &PPU, read_only: bool) -> u8 {
+        if self.strobe {
+            self.shift = self.buttons;
+        }
+
+        let data = self.shift >> 7;
+        if !read_only && !self.strobe {
+            self.shift = (self.shift << 1) | 0x01;
+        }
+        data
+    }
+
+    fn pad_mut(&mut self, slot: usize) -> Option<&mut Controller> {
+        (slot == 0).then_some(self)
+    }
+}
+
+// One port's half of a Four Score. A read takes 24 bits: the pad on this port, the extra
+// pad, then a signature that tells games the adapter is there. After that it reads 1.
+#[derive(Debug, Clone)]
+pub struct FourScore {
+    pub pads: [Controller; 2],
+    signature: u8,
+    shift: u32,
+    strobe: bool,
+}
+
+impl FourScore {
+    // port 0 carries pads 1 and 3, port 1 pads 2 and 4
+    pub fn new(port: usize) -> FourScore {
+        FourScore {
+            pads: [Controller::new(), Controller::new()],
+            signature: if port == 0 { 0x10 } else { 0x20 },
+            shift: 0,
+            strobe: false,
+        }
+    }
+
+    fn latch(&mut self) {
+        self.shift = u32::from(self.pads[0].buttons) << 16
+            | u32::from(self.pads[1].buttons) << 8
+            | u32::from(self.signature);
+    }
+}
+
+impl InputDevice for FourScore {
+    fn strobe(&mut self, on: bool) {
+        if self.strobe || on {
+            self.latch();
+        }
+        self.strobe = on;
+    }
+
+    fn read(&mut self, _ppu: &PPU, read_only: bool) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+
+        let data = ((self.shift >> 23) & 0x01) as u8;
+        if !read_only && !self.strobe {
+            self.shift = (self.shift << 1) | 0x01;
+        }
+        data
+    }
+
+    fn pad_mut(&mut self, slot: usize) -> Option<&mut Controller> {
+        self.pads.get_mut(slot)
+    }
+}
+
+// A Famicom port with a 4-player adapter on the expansion port: the built-in pad on D0,
+// the adapter's pad for the same port on D1.
+#[derive(Debug, Default, Clone)]
+pub struct Famicom4P {
+    pub pads: [Controller; 2],
+}
+
+impl Famicom4P {
+    pub fn new() -> Famicom4P {
+        Famicom4P::default()
+    }
+}
+
+impl InputDevice for Famicom4P {
+    fn strobe(&mut self, on: bool) {
+        for pad in &mut self.pads {
+            pad.strobe(on);
+        }
+    }
+
+    fn read(&mut self, ppu: &PPU, read_only: bool) -> u8 {
+        self.pads[0].read(ppu, read_only) | (self.pads[1].read(ppu, read_only) << 1)
+    }
+
+    fn pad_mut(&mut self, slot: usize) -> Option<&mut Controller> {
+        self.pads.get_mut(slot)
+    }
+}
+
+// The light gun: the trigger on D4, and D3 low while the photodiode sees light. The diode
+// looks at a few pixels around where it's aimed and picks up a bright one from the moment
+// the beam draws it until the phosphor fades, about LIGHT_SCANLINES later.
+#[derive(Debug, Default, Clone)]
+pub struct Zapper {
+    // screen pixel the gun points at, None when it's off screen
+    pub aim: Option<(i32, i32)>,
+    pub trigger: bool,
+}
+
+impl Zapper {
+    const RADIUS: i32 = 2;
+    const LIGHT_SCANLINES: i32 = 20;
+    // luma out of 255: white and the palest colors, not light blue sky
+    const BRIGHTNESS: u32 = 0xC0;
+
+    pub fn new() -> Zapper {
+        Zapper::default()
+    }
+
+    pub fn light_sensed(&self, ppu: &PPU) -> bool {
+        let Some((x, y)) = self.aim else {
+            return false;
+        };
+        let (scanline, dot) = (ppu.scanline(), ppu.dot());
+
+        for py in (y - Self::RADIUS)..=(y + Self::RADIUS) {
+            for px in (x - Self::RADIUS)..=(x + Self::RADIUS) {
+                if !(0..SCREEN_WIDTH as i32).contains(&px) || !(0..SCREEN_HEIGHT as i32).contains(&py) {
+                    continue;
+                }
+
+                // pixel x goes out on dot x + 1
+                let drawn = scanline > py || (scanline == py && dot > px);
+                if !drawn || scanline - py >= Self::LIGHT_SCANLINES {
+                    continue;
+                }
+
+                let i = (py as usize * SCREEN_WIDTH + px as usize) * 3;
+                let [r, g, b] = [0, 1, 2].map(|c| u32::from(ppu.screen[i + c]));
+                if (299 * r + 587 * g + 114 * b) / 1000 >= Self::BRIGHTNESS {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl InputDevice for Zapper {
+    fn strobe(&mut self, _on: bool) {}
+
+    fn read(&mut self, ppu: &PPU, _read_only: bool) -> u8 {
+        let light = if self.light_sensed(ppu) { 0x00 } else { 0x08 };
+        let trigger = if self.trigger { 0x10 } else { 0x00 };
+        light | trigger
+    }
+}
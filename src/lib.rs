@@ -3,4 +3,10 @@ pub mod mapper;
 pub mod cpu;
 pub mod ppu;
 pub mod bus;
+pub mod input;
 pub mod memory;
+pub mod region;
+pub mod diagnostics;
+pub mod nes;
+pub mod testrom;
+pub mod screenshot;
@@ -1,22 +1,33 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
 
-use rs6502::bus::Bus;
 use rs6502::cartridge::Cartridge;
-use rs6502::cpu::{CPU, Status};
-use rs6502::memory::Memory;
-use rs6502::ppu::PPU;
+use rs6502::cpu::{disasm, Status};
+use rs6502::diagnostics::Diagnostic;
+use rs6502::input::{Controller, InputType, Zapper};
+use rs6502::nes::Nes;
+use rs6502::region::Region;
 use olc_pixel_game_engine as olc;
 
 struct Emulator {
-    cpu: CPU,
-    ppu: Rc<RefCell<PPU>>,
+    nes: Nes,
     emulation_run: bool,
     residual_time: f32,
-    system_clock_counter: i32,
     selected_palette: u8,
-    _map_asm: HashMap<u16, String>,
+    map_asm: HashMap<u16, String>,
+    spr_screen: olc::Sprite,
+    spr_pattern_table: [olc::Sprite; 2],
+}
+
+// copy an RGB buffer from the PPU into a sprite to draw
+fn blit(sprite: &mut olc::Sprite, rgb: &[u8]) {
+    let width = sprite.width();
+    for (i, pixel) in rgb.chunks(3).enumerate() {
+        let (x, y) = (i as i32 % width, i as i32 / width);
+        sprite.set_pixel(x, y, olc::Pixel::rgb(pixel[0], pixel[1], pixel[2]));
+    }
 }
 
 impl Emulator {
@@ -31,11 +42,11 @@ impl Emulator {
         olc::draw_string(x + 160, y, "Z", self.get_color(Status::Z)).unwrap();
         olc::draw_string(x + 178, y, "C", self.get_color(Status::C)).unwrap();
 
-        olc::draw_string(x, y + 10, format!("PC: ${:04X}", self.cpu.pc).as_str(), olc::WHITE).unwrap();
-        olc::draw_string(x, y + 20, format!("A:  ${:02X}", self.cpu.a).as_str(), olc::WHITE).unwrap();
-        olc::draw_string(x, y + 30, format!("X:  ${:02X}", self.cpu.x).as_str(), olc::WHITE).unwrap();
-        olc::draw_string(x, y + 40, format!("Y:  ${:02X}", self.cpu.y).as_str(), olc::WHITE).unwrap();
-        olc::draw_string(x, y + 50, format!("SP: ${:02X}", self.cpu.sp).as_str(), olc::WHITE).unwrap();
+        olc::draw_string(x, y + 10, format!("PC: ${:04X}", self.nes.cpu.pc).as_str(), olc::WHITE).unwrap();
+        olc::draw_string(x, y + 20, format!("A:  ${:02X}", self.nes.cpu.a).as_str(), olc::WHITE).unwrap();
+        olc::draw_string(x, y + 30, format!("X:  ${:02X}", self.nes.cpu.x).as_str(), olc::WHITE).unwrap();
+        olc::draw_string(x, y + 40, format!("Y:  ${:02X}", self.nes.cpu.y).as_str(), olc::WHITE).unwrap();
+        olc::draw_string(x, y + 50, format!("SP: ${:02X}", self.nes.cpu.sp).as_str(), olc::WHITE).unwrap();
     }
 
     fn _draw_ram(&mut self, x: i32, y: i32, addr: &mut u16, rows: i32, cols: i32) {
@@ -44,54 +55,56 @@ impl Emulator {
         for _ in 0..rows {
             let mut offset = format!("${:04X}:", addr);
             for _ in 0..cols {
-                offset = format!("{} {:02X}", offset, self.cpu.read(*addr));
-                *addr = *addr + 1;
+                offset = format!("{} {:02X}", offset, self.nes.cpu.read(*addr));
+                *addr += 1;
             }
             olc::draw_string(ram_x, ram_y, &offset, olc::WHITE).unwrap();
             ram_y += 10;
         }
     }
 
-    fn _draw_code(&self, x: i32, y: i32, lines: i32) {
-        let mut pc = self.cpu.pc.clone();
+    // the disassembly around PC, which is highlighted in the middle
+    fn draw_code(&self, x: i32, y: i32, lines: i32) {
+        // the walks below only stop on lines they find
+        if self.map_asm.is_empty() {
+            return;
+        }
+
+        let mut pc = self.nes.cpu.pc;
         let mut line_y = (lines >> 1) * 10 + y;
 
-        match self._map_asm.get(&pc) {
-            Some(line) => {
-                olc::draw_string(x, line_y, line, olc::CYAN).unwrap();
-            }
-            None => ()
+        if let Some(line) = self.map_asm.get(&pc) {
+            olc::draw_string(x, line_y, line, olc::CYAN).unwrap();
         }
 
         while line_y < (lines * 10) + y {
             pc = pc.wrapping_add(1);
 
-            match self._map_asm.get(&pc) {
-                Some(line) => {
-                    line_y += 10;
-                    olc::draw_string(x, line_y, line, olc::WHITE).unwrap();
-                }
-                None => ()
+            if let Some(line) = self.map_asm.get(&pc) {
+                line_y += 10;
+                olc::draw_string(x, line_y, line, olc::WHITE).unwrap();
             }
         }
 
-        pc = self.cpu.pc.clone();
+        pc = self.nes.cpu.pc;
         line_y = (lines >> 1) * 10 + y;
         while line_y > y {
             pc = pc.wrapping_sub(1);
 
-            match self._map_asm.get(&pc) {
-                Some(line) => {
-                    line_y -= 10;
-                    olc::draw_string(x, line_y, line, olc::WHITE).unwrap();
-                }
-                None => ()
+            if let Some(line) = self.map_asm.get(&pc) {
+                line_y -= 10;
+                olc::draw_string(x, line_y, line, olc::WHITE).unwrap();
             }
         }
     }
 
+    fn swatch(&self, palette: u8, pixel: u8) -> olc::Pixel {
+        let [r, g, b] = self.nes.ppu().get_color_from_palette_ram(palette, pixel, self.nes.cartridge());
+        olc::Pixel::rgb(r, g, b)
+    }
+
     pub fn get_color(&self, s: Status) -> olc::Pixel {
-        if self.cpu.status.contains(s) {
+        if self.nes.cpu.status.contains(s) {
             olc::GREEN
         } else {
             olc::RED
@@ -99,49 +112,58 @@ impl Emulator {
     }
 
     pub fn clock(&mut self) {
-        self.ppu.borrow_mut().clock();
-
-        if self.system_clock_counter % 3 == 0 {
-            self.cpu.clock();
-        }
-
-        if self.ppu.borrow().nmi {
-            self.ppu.borrow_mut().nmi = false;
-            self.cpu.nmi();
-        }
-
-        self.system_clock_counter += 1;
+        self.nes.clock();
     }
 
     pub fn reset(&mut self) {
-        self.cpu.reset();
-        self.ppu.borrow_mut().reset();
-        self.system_clock_counter = 0;
+        self.nes.reset();
     }
 }
 
 impl olc::Application for Emulator {
     fn on_user_create(&mut self) -> Result<(), olc::Error> {
         self.reset();
-        //self.cpu.pc = 0xC001;
+        self.map_asm = disasm::disassemble_range(&mut self.nes.cpu.bus, 0x8000, 0xFFFF, self.nes.cpu.variant)
+            .into_iter()
+            .map(|line| (line.addr, line.to_string()))
+            .collect();
+        //self.nes.cpu.pc = 0xC001;
         Ok(())
     }
 
     fn on_user_update(&mut self, elapsed_time: f32) -> Result<(), olc::Error> {
         olc::clear(olc::DARK_BLUE);
 
-        self.cpu.bus.controller[0] = 0x00;
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::X).held { 0x80 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::Z).held { 0x40 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::A).held { 0x20 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::S).held { 0x10 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::UP).held { 0x08 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::DOWN).held { 0x04 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::LEFT).held { 0x02 } else { 0x00 };
-        self.cpu.bus.controller[0] |= if olc::get_key(olc::Key::RIGHT).held { 0x01 } else { 0x00 };
+        let keys = [
+            (olc::Key::X, Controller::A),
+            (olc::Key::Z, Controller::B),
+            (olc::Key::A, Controller::SELECT),
+            (olc::Key::S, Controller::START),
+            (olc::Key::UP, Controller::UP),
+            (olc::Key::DOWN, Controller::DOWN),
+            (olc::Key::LEFT, Controller::LEFT),
+            (olc::Key::RIGHT, Controller::RIGHT),
+        ];
+        if let Some(pad) = self.nes.cpu.bus.pad_mut(0) {
+            pad.buttons = keys
+                .iter()
+                .filter(|(key, _)| olc::get_key(*key).held)
+                .fold(0, |buttons, (_, button)| buttons | button);
+        }
+
+        // the picture is drawn at twice its size in the top left corner
+        if let Some(zapper) = self.nes.cpu.bus.device_mut::<Zapper>(1) {
+            let (x, y) = (olc::get_mouse_x() / 2, olc::get_mouse_y() / 2);
+            let on_screen = (0..256).contains(&x) && (0..240).contains(&y);
+            zapper.aim = on_screen.then_some((x, y));
+            zapper.trigger = olc::get_mouse(0).held;
+        }
 
         if olc::get_key(olc::Key::SPACE).pressed { self.emulation_run = !self.emulation_run }
         if olc::get_key(olc::Key::R).pressed { self.reset(); }
+        if olc::get_key(olc::Key::T).pressed && self.nes.cpu.trace.is_some() {
+            self.nes.cpu.trace_enabled = !self.nes.cpu.trace_enabled;
+        }
         if olc::get_key(olc::Key::P).pressed {
             self.selected_palette += 1;
             self.selected_palette &= 0x07;
@@ -152,25 +174,19 @@ impl olc::Application for Emulator {
                 self.residual_time -= elapsed_time;
             } else {
                 self.residual_time += (1.0 / 60.0) - elapsed_time;
-                loop {
-                    self.clock();
-                    if self.ppu.borrow().frame_complete {
-                        break;
-                    }
-                }
-                self.ppu.borrow_mut().frame_complete = false;
+                self.nes.run_frame();
             }
         } else {
-            if olc::get_key(olc::Key::C).pressed {
+            if olc::get_key(olc::Key::C).pressed && !self.nes.cpu.halted() {
                 loop {
                     self.clock();
-                    if self.cpu.complete() {
+                    if self.nes.cpu.complete() || self.nes.cpu.halted() {
                         break;
                     }
                 }
                 loop {
                     self.clock();
-                    if !self.cpu.complete() {
+                    if !self.nes.cpu.complete() {
                         break;
                     }
                 }
@@ -178,48 +194,53 @@ impl olc::Application for Emulator {
             if olc::get_key(olc::Key::F).pressed {
                 loop {
                     self.clock();
-                    if self.ppu.borrow().frame_complete {
+                    if self.nes.frame_complete() {
                         break;
                     }
                 }
 
                 loop {
                     self.clock();
-                    if self.cpu.complete() {
+                    if self.nes.cpu.complete() || self.nes.cpu.halted() {
                         break;
                     }
                 }
 
-                self.ppu.borrow_mut().frame_complete = false;
+                self.nes.cpu.bus.frame_complete = false;
             }
         }
 
         self.draw_cpu(516, 2);
-        //self.draw_code(516, 72, 26);
+        olc::draw_string(516, 64, format!("FRAME: {}", self.nes.ppu().frame_number()).as_str(), olc::WHITE).unwrap();
+        self.draw_code(516, 72, 26);
         //self.draw_ram(516, 100, &mut 0x0000, 16, 16);
         //self.draw_ram(516, 300, &mut 0x8000, 16, 16);
 
         let swatch_size = 6;
         for p in 0..8 {
             for s in 0..4 {
-                olc::fill_rect(516 + p * (swatch_size * 5) + s * swatch_size, 340, swatch_size, swatch_size, self.ppu.borrow().get_color_from_palette_ram(p.try_into().unwrap(), s.try_into().unwrap()));
+                olc::fill_rect(516 + p * (swatch_size * 5) + s * swatch_size, 340, swatch_size, swatch_size, self.swatch(p.try_into().unwrap(), s.try_into().unwrap()));
             }
         }
         olc::draw_rect(516 + i32::from(self.selected_palette) * (swatch_size * 5) - 1, 339, swatch_size * 4, swatch_size, olc::WHITE);
 
-        self.ppu.borrow_mut().build_pattern_table(0, self.selected_palette);
-        self.ppu.borrow_mut().build_pattern_table(1, self.selected_palette);
+        let bus = &mut self.nes.cpu.bus;
+        for i in 0..2 {
+            bus.ppu.build_pattern_table(i, self.selected_palette, &bus.cartridge);
+            blit(&mut self.spr_pattern_table[i as usize], bus.ppu.get_pattern_table(i));
+        }
 
-        olc::draw_sprite(516, 348, self.ppu.borrow().get_pattern_table(0));
-        olc::draw_sprite(648, 348, self.ppu.borrow().get_pattern_table(1));
+        olc::draw_sprite(516, 348, &self.spr_pattern_table[0]);
+        olc::draw_sprite(648, 348, &self.spr_pattern_table[1]);
 
-        olc::draw_sprite_ext(0, 0, &self.ppu.borrow().spr_screen, 2, olc_pixel_game_engine::SpriteFlip::NONE);
+        blit(&mut self.spr_screen, &self.nes.ppu().screen);
+        olc::draw_sprite_ext(0, 0, &self.spr_screen, 2, olc_pixel_game_engine::SpriteFlip::NONE);
 
         // for y in 0..30 {
         //     for x in 0..32 {
-        //         //olc::draw_string(x * 16, y * 16, &format!("{:02X}", self.ppu.borrow().tbl_name[0][(y * 32 + x) as usize]), olc::WHITE).unwrap();
-        //         let id = self.ppu.borrow().tbl_name[0][(y * 32 + x) as usize];
-        //         olc::draw_partial_sprite_ext(x * 16, y * 16, self.ppu.borrow().get_pattern_table(1), i32::from(id & 0x0F) << 3, i32::from((id >> 4) & 0x0F) << 3, 8, 8, 2, olc::SpriteFlip::NONE);
+        //         //olc::draw_string(x * 16, y * 16, &format!("{:02X}", self.nes.ppu().tbl_name[0][(y * 32 + x) as usize]), olc::WHITE).unwrap();
+        //         let id = self.nes.ppu().tbl_name[0][(y * 32 + x) as usize];
+        //         olc::draw_partial_sprite_ext(x * 16, y * 16, &self.spr_pattern_table[1], i32::from(id & 0x0F) << 3, i32::from((id >> 4) & 0x0F) << 3, 8, 8, 2, olc::SpriteFlip::NONE);
         //     }
         // }
 
@@ -227,31 +248,64 @@ impl olc::Application for Emulator {
     }
 
     fn on_user_destroy(&mut self) -> Result<(), olc::Error> {
+        if let Some(out) = self.nes.cpu.trace.as_mut() {
+            let _ = out.flush();
+        }
         Ok(())
     }
 }
 
 fn main() {
-    let cartridge = Rc::new(RefCell::new(Cartridge::new(String::from("nestest.nes"))));
-
-    let ppu = Rc::new(RefCell::new(PPU::new(cartridge.clone())));
+    let mut rom = String::from("nestest.nes");
+    let mut region_override = None;
+    let mut trace = None;
+    let mut input_override = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => {
+                let path = args.next().expect("--trace needs a file to write to");
+                trace = Some(BufWriter::new(File::create(&path).unwrap()));
+            },
+            "--input" => {
+                let input = args.next().expect("--input needs one of standard, fourscore, famicom4p, zapper");
+                input_override = Some(input.parse::<InputType>().unwrap());
+            },
+            "--region" => {
+                let region = args.next().expect("--region needs one of ntsc, pal, dendy");
+                region_override = Some(region.parse::<Region>().unwrap());
+            },
+            _ => rom = arg,
+        }
+    }
 
-    let bus = Bus {
-        cartridge: cartridge.clone(),
-        memory: Memory::new(),
-        ppu: ppu.clone(),
-        controller: [0; 2],
-        controller_state: [0; 2],
+    let cartridge = match Cartridge::new(rom) {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        },
     };
-    let cpu = CPU::new(bus);
+    let mut nes = Nes::new(cartridge);
+    if let Some(region) = region_override {
+        nes.set_region(region);
+    }
+    if let Some(input) = input_override {
+        nes.cpu.bus.set_input(input);
+    }
+    nes.cpu.bus.diagnostics = Some(Arc::new(|diagnostic: Diagnostic| eprintln!("{diagnostic}")));
+    if let Some(out) = trace {
+        nes.cpu.trace_to(out);
+    }
     let mut emulator = Emulator {
-        cpu,
-        ppu: ppu.clone(),
+        nes,
         emulation_run: false,
         residual_time: 0f32,
-        system_clock_counter: 0,
         selected_palette: 0,
-        _map_asm: HashMap::new(),
+        map_asm: HashMap::new(),
+        spr_screen: olc::Sprite::with_dims(256, 240),
+        spr_pattern_table: [olc::Sprite::with_dims(128, 128), olc::Sprite::with_dims(128, 128)],
     };
     olc::start("nes", &mut emulator, 780, 480, 2, 2).unwrap();
 }
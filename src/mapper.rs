@@ -41,10 +41,8 @@ impl Mapper {
     }
 
     pub fn ppu_map_write(&self, addr: u16) -> (bool, u32) {
-        if addr <= 0x1FFF {
-            if self.chr_banks == 0 {
-                return (true, addr.into());
-            }
+        if addr <= 0x1FFF && self.chr_banks == 0 {
+            return (true, addr.into());
         }
 
         (false, 0)
@@ -1,7 +1,17 @@
+use crate::bus::CpuBus;
+use crate::diagnostics::{Diagnostic, DiagnosticHandler};
+
+#[derive(Clone)]
 pub struct Memory {
     bytes: [u8; 2048],
 }
 
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Memory {
     pub const fn new() -> Memory {
         Memory {
@@ -17,3 +27,91 @@ impl Memory {
         self.bytes[address as usize] = value;
     }
 }
+
+// A bare 64 KB address space, RAM all the way through, for running plain 6502 code
+// outside of the NES.
+pub struct FlatMemory {
+    bytes: Vec<u8>,
+    pub nmi: bool,
+    pub irq: bool,
+    pub diagnostics: Option<DiagnosticHandler>,
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatMemory {
+    // start of the ROM area in linker.cfg
+    pub const ROM_START: u16 = 0x8000;
+
+    pub fn new() -> FlatMemory {
+        FlatMemory {
+            bytes: vec![0; 0x10000],
+            nmi: false,
+            irq: false,
+            diagnostics: None,
+        }
+    }
+
+    // load a raw binary linked with linker.cfg: the image is the ROM area, ZP and RAM
+    // start out cleared
+    pub fn from_rom(image: &[u8]) -> Result<FlatMemory, String> {
+        let mut mem = FlatMemory::new();
+        mem.load(Self::ROM_START, image)?;
+
+        // images that stop short of the vectors start executing at the top of ROM
+        if image.len() < 0x7FFE {
+            mem.set_reset_vector(Self::ROM_START);
+        }
+
+        Ok(mem)
+    }
+
+    pub fn load(&mut self, addr: u16, data: &[u8]) -> Result<(), String> {
+        let start = addr as usize;
+        let end = start + data.len();
+        if end > self.bytes.len() {
+            return Err(format!("{} bytes at {addr:04X} run past the end of memory", data.len()));
+        }
+
+        self.bytes[start..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn set_reset_vector(&mut self, addr: u16) {
+        let [lo, hi] = addr.to_le_bytes();
+        self.bytes[0xFFFC] = lo;
+        self.bytes[0xFFFD] = hi;
+    }
+}
+
+impl CpuBus for FlatMemory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.bytes[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.bytes[addr as usize] = data;
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.bytes[addr as usize]
+    }
+
+    fn nmi_pending(&mut self) -> bool {
+        std::mem::take(&mut self.nmi)
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq
+    }
+
+    fn report(&self, diagnostic: Diagnostic) {
+        if let Some(handler) = &self.diagnostics {
+            handler(diagnostic);
+        }
+    }
+}
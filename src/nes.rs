@@ -0,0 +1,70 @@
+use crate::bus::Bus;
+use crate::cartridge::Cartridge;
+use crate::cpu::CPU;
+use crate::ppu::PPU;
+use crate::region::Region;
+
+// The whole console, without any window around it. Everything hangs off the CPU's bus, so
+// a clone is a save state that can be run on independently, on any thread.
+#[derive(Clone)]
+pub struct Nes {
+    pub cpu: CPU,
+}
+
+impl Nes {
+    pub fn new(cartridge: Cartridge) -> Nes {
+        let ppu = PPU::new(cartridge.region);
+        Nes {
+            cpu: CPU::new(Bus::new(cartridge, ppu)),
+        }
+    }
+
+    pub fn ppu(&self) -> &PPU {
+        &self.cpu.bus.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut PPU {
+        &mut self.cpu.bus.ppu
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cpu.bus.cartridge
+    }
+
+    pub fn region(&self) -> Region {
+        self.cpu.bus.region()
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.cpu.bus.set_region(region);
+    }
+
+    // one CPU clock, with the PPU brought right up to date for anyone looking at it
+    pub fn clock(&mut self) {
+        self.cpu.clock();
+        self.cpu.bus.sync();
+    }
+
+    // the reset button: RAM and cartridge RAM keep their contents
+    pub fn reset(&mut self) {
+        self.cpu.bus.sync();
+        self.cpu.bus.ppu.reset();
+        self.cpu.bus.sync();
+        self.cpu.reset();
+    }
+
+    // the PPU is left at the start of the pre-render line, with the picture complete
+    pub fn run_frame(&mut self) {
+        while !self.cpu.bus.frame_complete {
+            // the bus is already past an instruction's cycles by the time clock() returns,
+            // so its idle clocks only count down; start the next one straight away
+            self.cpu.cycles_remaining = 0;
+            self.cpu.clock();
+        }
+        self.cpu.bus.frame_complete = false;
+    }
+
+    pub fn frame_complete(&self) -> bool {
+        self.cpu.bus.frame_complete
+    }
+}
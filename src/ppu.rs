@@ -1,27 +1,116 @@
-use std::{cell::RefCell, rc::Rc};
 use bitflags::bitflags;
 
-use olc_pixel_game_engine as olc;
-
 use crate::cartridge::{Cartridge, Mirror};
+use crate::region::Region;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+// RGB for each of the 64 colors the PPU can put out
+pub const SYSTEM_PALETTE: [[u8; 3]; 0x40] = [
+    [84, 84, 84],
+    [0, 30, 116],
+    [8, 16, 144],
+    [48, 0, 136],
+    [68, 0, 100],
+    [92, 0, 48],
+    [84, 4, 0],
+    [60, 24, 0],
+    [32, 42, 0],
+    [8, 58, 0],
+    [0, 64, 0],
+    [0, 60, 0],
+    [0, 50, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+
+    [152, 150, 152],
+    [8, 76, 196],
+    [48, 50, 236],
+    [92, 30, 228],
+    [136, 20, 176],
+    [160, 20, 100],
+    [152, 34, 32],
+    [120, 60, 0],
+    [84, 90, 0],
+    [40, 114, 0],
+    [8, 124, 0],
+    [0, 118, 40],
+    [0, 102, 120],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+
+    [236, 238, 236],
+    [76, 154, 236],
+    [120, 124, 236],
+    [176, 98, 236],
+    [228, 84, 236],
+    [236, 88, 180],
+    [236, 106, 100],
+    [212, 136, 32],
+    [160, 170, 0],
+    [116, 196, 0],
+    [76, 208, 32],
+    [56, 204, 108],
+    [56, 180, 204],
+    [60, 60, 60],
+    [0, 0, 0],
+    [0, 0, 0],
+
+    [236, 238, 236],
+    [168, 204, 236],
+    [188, 188, 236],
+    [212, 178, 236],
+    [236, 174, 236],
+    [236, 174, 212],
+    [236, 180, 176],
+    [228, 196, 144],
+    [204, 210, 120],
+    [180, 222, 120],
+    [168, 226, 144],
+    [152, 226, 180],
+    [160, 214, 228],
+    [160, 162, 160],
+    [0, 0, 0],
+    [0, 0, 0],
+];
+
+// the bits of a pattern byte spread out one per byte, leftmost pixel in the lowest byte
+const PIXEL_BITS: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            table[i] |= ((i as u64 >> (7 - bit)) & 0x01) << (bit * 8);
+            bit += 1;
+        }
+        i += 1;
+    }
+    table
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PPU {
     pub tbl_name: [[u8; 1024]; 2],
     tbl_pattern: [[u8; 4096]; 2],
     tbl_palette: [u8; 32],
 
-    pal_screen: Vec<olc::Pixel>,
-
-    pub spr_screen: olc::Sprite,
-    _spr_name_table: [olc::Sprite; 2],
-    pub spr_pattern_table: [olc::Sprite; 2],
+    // RGB, row major
+    pub screen: Vec<u8>,
+    pattern_tables: [Vec<u8>; 2],
 
     pub frame_complete: bool,
+    clock_count: u64,
+    frame: u32,
     scanline: i32,
     cycle: i32,
+    region: Region,
+    vblank_scanline: i32,
+    prerender_scanline: i32,
 
-    cart: Rc<RefCell<Cartridge>>,
 
     status: Status,
     mask: Mask,
@@ -29,9 +118,11 @@ pub struct PPU {
     scroll: Scroll,
     pub nmi: bool,
 
-    fine_x: u8,
     vram_buffer: u8,
-    open_bus: u8,
+    open_bus: OpenBus,
+
+    oam: [u8; 256],
+    oam_addr: u8,
 
     prev_palette: u8,
     curr_palette: u8,
@@ -58,7 +149,7 @@ bitflags! {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Status {
     pub spr_overflow: bool,
     pub spr_zero_hit: bool,
@@ -104,7 +195,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Mask {
     pub rendering_enabled: bool,
     pub grayscale: u8,
@@ -155,7 +246,7 @@ bitflags! {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Control {
     pub spr_select: u16,
     pub bg_select: u16,
@@ -192,7 +283,7 @@ impl Control {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Scroll {
     pub fine_x: u16,
     pub coarse_x: u16,
@@ -205,6 +296,12 @@ pub struct Scroll {
     delay_v: u16,
 }
 
+impl Default for Scroll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Scroll {
     pub const fn new() -> Self {
         Self {
@@ -297,109 +394,86 @@ impl Scroll {
 
 }
 
-impl PPU {
-    pub fn new(cart: Rc<RefCell<Cartridge>>) -> PPU {
-        let mut pal_screen = Vec::new();
-        pal_screen.resize(0x40, olc::Pixel::rgb(0, 0, 0));
-
-        pal_screen[0x00] = olc::Pixel::rgb(84, 84, 84);
-        pal_screen[0x01] = olc::Pixel::rgb(0, 30, 116);
-        pal_screen[0x02] = olc::Pixel::rgb(8, 16, 144);
-        pal_screen[0x03] = olc::Pixel::rgb(48, 0, 136);
-        pal_screen[0x04] = olc::Pixel::rgb(68, 0, 100);
-        pal_screen[0x05] = olc::Pixel::rgb(92, 0, 48);
-        pal_screen[0x06] = olc::Pixel::rgb(84, 4, 0);
-        pal_screen[0x07] = olc::Pixel::rgb(60, 24, 0);
-        pal_screen[0x08] = olc::Pixel::rgb(32, 42, 0);
-        pal_screen[0x09] = olc::Pixel::rgb(8, 58, 0);
-        pal_screen[0x0A] = olc::Pixel::rgb(0, 64, 0);
-        pal_screen[0x0B] = olc::Pixel::rgb(0, 60, 0);
-        pal_screen[0x0C] = olc::Pixel::rgb(0, 50, 60);
-        pal_screen[0x0D] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x0E] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x0F] = olc::Pixel::rgb(0, 0, 0);
-
-        pal_screen[0x10] = olc::Pixel::rgb(152, 150, 152);
-        pal_screen[0x11] = olc::Pixel::rgb(8, 76, 196);
-        pal_screen[0x12] = olc::Pixel::rgb(48, 50, 236);
-        pal_screen[0x13] = olc::Pixel::rgb(92, 30, 228);
-        pal_screen[0x14] = olc::Pixel::rgb(136, 20, 176);
-        pal_screen[0x15] = olc::Pixel::rgb(160, 20, 100);
-        pal_screen[0x16] = olc::Pixel::rgb(152, 34, 32);
-        pal_screen[0x17] = olc::Pixel::rgb(120, 60, 0);
-        pal_screen[0x18] = olc::Pixel::rgb(84, 90, 0);
-        pal_screen[0x19] = olc::Pixel::rgb(40, 114, 0);
-        pal_screen[0x1A] = olc::Pixel::rgb(8, 124, 0);
-        pal_screen[0x1B] = olc::Pixel::rgb(0, 118, 40);
-        pal_screen[0x1C] = olc::Pixel::rgb(0, 102, 120);
-        pal_screen[0x1D] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x1E] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x1F] = olc::Pixel::rgb(0, 0, 0);
-
-        pal_screen[0x20] = olc::Pixel::rgb(236, 238, 236);
-        pal_screen[0x21] = olc::Pixel::rgb(76, 154, 236);
-        pal_screen[0x22] = olc::Pixel::rgb(120, 124, 236);
-        pal_screen[0x23] = olc::Pixel::rgb(176, 98, 236);
-        pal_screen[0x24] = olc::Pixel::rgb(228, 84, 236);
-        pal_screen[0x25] = olc::Pixel::rgb(236, 88, 180);
-        pal_screen[0x26] = olc::Pixel::rgb(236, 106, 100);
-        pal_screen[0x27] = olc::Pixel::rgb(212, 136, 32);
-        pal_screen[0x28] = olc::Pixel::rgb(160, 170, 0);
-        pal_screen[0x29] = olc::Pixel::rgb(116, 196, 0);
-        pal_screen[0x2A] = olc::Pixel::rgb(76, 208, 32);
-        pal_screen[0x2B] = olc::Pixel::rgb(56, 204, 108);
-        pal_screen[0x2C] = olc::Pixel::rgb(56, 180, 204);
-        pal_screen[0x2D] = olc::Pixel::rgb(60, 60, 60);
-        pal_screen[0x2E] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x2F] = olc::Pixel::rgb(0, 0, 0);
-
-        pal_screen[0x30] = olc::Pixel::rgb(236, 238, 236);
-        pal_screen[0x31] = olc::Pixel::rgb(168, 204, 236);
-        pal_screen[0x32] = olc::Pixel::rgb(188, 188, 236);
-        pal_screen[0x33] = olc::Pixel::rgb(212, 178, 236);
-        pal_screen[0x34] = olc::Pixel::rgb(236, 174, 236);
-        pal_screen[0x35] = olc::Pixel::rgb(236, 174, 212);
-        pal_screen[0x36] = olc::Pixel::rgb(236, 180, 176);
-        pal_screen[0x37] = olc::Pixel::rgb(228, 196, 144);
-        pal_screen[0x38] = olc::Pixel::rgb(204, 210, 120);
-        pal_screen[0x39] = olc::Pixel::rgb(180, 222, 120);
-        pal_screen[0x3A] = olc::Pixel::rgb(168, 226, 144);
-        pal_screen[0x3B] = olc::Pixel::rgb(152, 226, 180);
-        pal_screen[0x3C] = olc::Pixel::rgb(160, 214, 228);
-        pal_screen[0x3D] = olc::Pixel::rgb(160, 162, 160);
-        pal_screen[0x3E] = olc::Pixel::rgb(0, 0, 0);
-        pal_screen[0x3F] = olc::Pixel::rgb(0, 0, 0);
-
-        let ppu = PPU {
-            pal_screen,
+// The PPU's I/O latch. Every bit is a capacitor that is charged by CPU reads
+// and writes of $2000-$2007 and slowly leaks back to 0 if left alone.
+#[derive(Debug, Clone)]
+pub struct OpenBus {
+    latch: u8,
+    refreshed: [u64; 8],
+    decay_cycles: u64,
+}
 
+impl OpenBus {
+    const DECAY_MS: u64 = 600;
+
+    pub fn new(region: Region) -> Self {
+        let mut open_bus = Self {
+            latch: 0,
+            refreshed: [0; 8],
+            decay_cycles: 0,
+        };
+        open_bus.set_region(region);
+        open_bus
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        let ppu_clock_hz = region.master_clock_hz() / region.ppu_divider();
+        self.decay_cycles = ppu_clock_hz * Self::DECAY_MS / 1000;
+    }
+
+    // drive the bits in `mask` with `val`, recharging them
+    pub fn refresh(&mut self, val: u8, mask: u8, now: u64) {
+        self.latch = (self.latch & !mask) | (val & mask);
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 {
+                self.refreshed[bit] = now;
+            }
+        }
+    }
+
+    pub fn write(&mut self, val: u8, now: u64) {
+        self.refresh(val, 0xFF, now);
+    }
+
+    pub fn read(&self, now: u64) -> u8 {
+        let mut val = self.latch;
+        for bit in 0..8 {
+            if now.wrapping_sub(self.refreshed[bit]) > self.decay_cycles {
+                val &= !(1 << bit);
+            }
+        }
+        val
+    }
+}
+
+impl PPU {
+    pub fn new(region: Region) -> PPU {
+        PPU {
             tbl_name: [[0; 1024]; 2],
             tbl_pattern: [[0; 4096]; 2],
             tbl_palette: [0; 32],
 
-            spr_screen: olc::Sprite::with_dims(256, 240),
-            _spr_name_table: [
-                olc::Sprite::with_dims(256, 240),
-                olc::Sprite::with_dims(256, 240),
-            ],
-            spr_pattern_table: [
-                olc::Sprite::with_dims(128, 128),
-                olc::Sprite::with_dims(128, 128),
-            ],
+            screen: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3],
+            pattern_tables: [vec![0; 128 * 128 * 3], vec![0; 128 * 128 * 3]],
 
             frame_complete: false,
+            clock_count: 0,
+            frame: 0,
             scanline: 0,
             cycle: 0,
-            cart,
+            region,
+            vblank_scanline: region.vblank_scanline(),
+            prerender_scanline: region.prerender_scanline(),
 
             status: Status::new(),
             mask: Mask::new(),
             control: Control::new(),
             scroll: Scroll::new(),
             vram_buffer: 0,
-            open_bus: 0,
+            open_bus: OpenBus::new(region),
+            oam: [0; 256],
+            oam_addr: 0,
             nmi: false,
-            fine_x: 0,
             prev_palette: 0,
             curr_palette: 0,
             next_palette: 0,
@@ -408,19 +482,70 @@ impl PPU {
             tile_addr: 0,
             tile_shift_hi: 0,
             tile_shift_lo: 0,
-        };
+        }
+    }
 
-        ppu
+    pub fn region(&self) -> Region {
+        self.region
     }
 
-    pub fn cpu_read(&mut self, addr: u16, read_only: bool) -> u8 {
-        if read_only {
-            println!("read only");
-        }
+    // the v, t, fine x and w latches behind $2005/$2006, for debuggers and tests
+    pub fn scroll(&self) -> &Scroll {
+        &self.scroll
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.vblank_scanline = region.vblank_scanline();
+        self.prerender_scanline = region.prerender_scanline();
+        self.open_bus.set_region(region);
+    }
+
+    // scanline being drawn: 0-239 are visible, the last one of the frame is the pre-render line
+    pub fn scanline(&self) -> i32 {
+        self.scanline
+    }
+
+    // dot within the current scanline, 0-340
+    pub fn dot(&self) -> i32 {
+        self.cycle
+    }
+
+    // number of the frame currently being drawn, starting from 0 at reset
+    pub fn frame_number(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn odd_frame(&self) -> bool {
+        self.frame & 0x01 == 0x01
+    }
+
+    // dots until vblank starts or the frame ends, whichever is first. The dot NTSC skips on
+    // odd frames isn't known for sure this far ahead, so this can come up one dot early,
+    // never late.
+    pub fn dots_to_next_event(&self) -> u64 {
+        const DOTS_PER_LINE: i32 = 341;
+        let frame = (self.prerender_scanline + 1) * DOTS_PER_LINE;
+        let now = self.scanline * DOTS_PER_LINE + self.cycle;
+
+        let dots = [self.vblank_scanline, self.prerender_scanline]
+            .into_iter()
+            .map(|scanline| {
+                let dots = (scanline * DOTS_PER_LINE + 1 - now).rem_euclid(frame);
+                if dots == 0 { frame } else { dots }
+            })
+            .min()
+            .unwrap_or(1);
+
+        (dots - 1).max(1) as u64
+    }
+
+    pub fn cpu_read(&mut self, addr: u16, read_only: bool, cart: &Cartridge) -> u8 {
+        let now = self.clock_count;
 
-        match addr {
-            0x0000 => 0x00,
-            0x0001 => 0x00,
+        match addr & 0x0007 {
+            // write-only registers read back whatever is left on the latch
+            0x0000 | 0x0001 | 0x0003 | 0x0005 | 0x0006 => self.open_bus.read(now),
             0x0002 => {
                 let data = self.peek_status();
                 if read_only {
@@ -429,58 +554,68 @@ impl PPU {
 
                 self.stop_vblank();
                 self.scroll.write_latch = false;
+                self.open_bus.refresh(data, 0xE0, now);
 
-                self.open_bus |= data & 0xE0;
+                data
+            },
+            0x0004 => {
+                let data = self.peek_oam();
+                if !read_only {
+                    self.open_bus.write(data, now);
+                }
 
                 data
             },
-            0x0003 => 0x00,
-            0x0004 => 0x00,
-            0x0005 => 0x00,
-            0x0006 => 0x00,
             0x0007 => {
+                let addr = self.scroll.addr();
                 if read_only {
-                    return 0x00;
+                    return if addr < 0x3F00 {
+                        self.vram_buffer
+                    } else {
+                        self.ppu_read(addr, cart) | (self.open_bus.read(now) & 0xC0)
+                    };
                 }
 
-                let addr = self.scroll.addr();
                 self.increment_vram_addr();
 
-                let val= self.ppu_read(addr);
-                let val = if addr < 0x3F00 {
+                let val = self.ppu_read(addr, cart);
+                if addr < 0x3F00 {
                     let buffer = self.vram_buffer;
                     self.vram_buffer = val;
+                    self.open_bus.write(buffer, now);
                     buffer
                 } else {
-                    self.vram_buffer = self.ppu_read(addr - 0x1000);
-                    val | (self.open_bus & 0xC0)
-                };
-
-                self.open_bus = val;
-
-                val
+                    // palette reads only drive the low 6 bits
+                    self.vram_buffer = self.ppu_read(addr - 0x1000, cart);
+                    self.open_bus.refresh(val, 0x3F, now);
+                    self.open_bus.read(now)
+                }
             },
-            _ => panic!("invalid CPU read from PPU")
+            _ => unreachable!("PPU registers are 3 bits wide"),
         }
     }
 
-    pub fn cpu_write(&mut self, addr: u16, data: u8) {
-        match addr {
+    pub fn cpu_write(&mut self, addr: u16, data: u8, cart: &mut Cartridge) {
+        // every write, even to $2002, charges the whole latch
+        self.open_bus.write(data, self.clock_count);
+
+        match addr & 0x0007 {
             0x0000 => {
-                self.open_bus = data;
                 self.control.write(data);
                 self.scroll.write_nametable_select(data);
             },
             0x0001 => {
-                self.open_bus = data;
                 self.mask.write(data);
             },
             0x0002 => (),
-            0x0003 => (),
-            0x0004 => (),
+            0x0003 => {
+                self.oam_addr = data;
+            },
+            0x0004 => {
+                self.oam[self.oam_addr as usize] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            },
             0x0005 => {
-                self.open_bus = data;
-
                 let val = u16::from(data);
                 let lo_5_bit_mask: u16 = 0x1F;
                 let fine_mask: u16 = 0x07;
@@ -501,7 +636,6 @@ impl PPU {
                 self.scroll.write_latch = !self.scroll.write_latch;
             },
             0x0006 => {
-                self.open_bus = data;
                 if self.scroll.write_latch {
                     let lo_bits_mask = 0x7F00;
                     self.scroll.t = (self.scroll.t & lo_bits_mask) | u16::from(data);
@@ -516,111 +650,75 @@ impl PPU {
                 self.scroll.write_latch = !self.scroll.write_latch;
             },
             0x0007 => {
-                self.open_bus = data;
                 let addr = self.scroll.addr();
                 self.increment_vram_addr();
-                self.ppu_write(addr, data);
+                self.ppu_write(addr, data, cart);
             },
-            _ => panic!("invalid CPU write from PPU")
+            _ => unreachable!("PPU registers are 3 bits wide"),
         }
     }
 
-    pub fn ppu_read(&self, addr: u16) -> u8 {
+    pub fn ppu_read(&self, addr: u16, cart: &Cartridge) -> u8 {
         let addr = addr & 0x3FFF;
 
-        if let (true, data) = self.cart.borrow().ppu_read(addr) {
-            return data;
+        if let (true, data) = cart.ppu_read(addr) {
+            data
         } else if addr <= 0x1FFF {
             let idx1 = (addr & 0x1000) >> 12;
             let idx2 = addr & 0x0FFF;
 
-            return self.tbl_pattern[idx1 as usize][idx2 as usize];
-        } else if addr >= 0x2000 && addr <= 0x3EFF {
-            let addr = addr & 0x0FFF;
-            let idx = (addr & 0x03FF) as usize;
-
-            if self.cart.borrow().mirror == Mirror::VERTICAL {
-                match addr {
-                    0x0000..=0x03FF => self.tbl_name[0][idx],
-                    0x0400..=0x07FF => self.tbl_name[1][idx],
-                    0x0800..=0x0BFF => self.tbl_name[0][idx],
-                    0x0C00..=0x0FFF => self.tbl_name[1][idx],
-                    _ => panic!("invalid nametable read 1"),
-                }
-            } else if self.cart.borrow().mirror == Mirror::HORIZONTAL {
-                match addr {
-                    0x0000..=0x03FF => self.tbl_name[0][idx],
-                    0x0400..=0x07FF => self.tbl_name[0][idx],
-                    0x0800..=0x0BFF => self.tbl_name[1][idx],
-                    0x0C00..=0x0FFF => self.tbl_name[1][idx],
-                    x => {
-                        panic!("invalid nametable read 2, from: {x:04X}");
-                    },
-                }
-            } else {
-                panic!("invalid mirroring read");
-            }
-        } else if addr >= 0x3F00 && addr <= 0x3FFF {
-            let mut addr = addr & 0x001F;
-            if addr == 0x0010 { addr = 0x0000 };
-            if addr == 0x0014 { addr = 0x0004 };
-            if addr == 0x0018 { addr = 0x0008 };
-            if addr == 0x001C { addr = 0x000C };
-
-            return self.tbl_palette[addr as usize];
+            self.tbl_pattern[idx1 as usize][idx2 as usize]
+        } else if (0x2000..=0x3EFF).contains(&addr) {
+            let (table, idx) = Self::nametable_index(addr, cart.mirror);
+            self.tbl_name[table][idx]
         } else {
-            panic!("bad PPU read")
+            // $3F00-$3FFF, the address was masked to 14 bits above
+            self.tbl_palette[Self::palette_index(addr)]
         }
     }
 
-    pub fn ppu_write(&mut self, addr: u16, data: u8) {
+    pub fn ppu_write(&mut self, addr: u16, data: u8, cart: &mut Cartridge) {
         let addr = addr & 0x3FFF;
 
-        if self.cart.borrow_mut().ppu_write(addr, data) {
+        if cart.ppu_write(addr, data) {
             // done
         } else if addr <= 0x1FFF {
             let idx1 = (addr & 0x1000) >> 12;
             let idx2 = addr & 0x0FFF;
 
             self.tbl_pattern[idx1 as usize][idx2 as usize] = data;
-        } else if addr >= 0x2000 && addr <= 0x3EFF {
-            let addr = addr & 0x0FFF;
-            let idx = (addr & 0x03FF) as usize;
-
-            if self.cart.borrow().mirror == Mirror::VERTICAL {
-                match addr {
-                    0x0000..=0x03FF => self.tbl_name[0][idx] = data,
-                    0x0400..=0x07FF => self.tbl_name[1][idx] = data,
-                    0x0800..=0x0BFF => self.tbl_name[0][idx] = data,
-                    0x0C00..=0x0FFF => self.tbl_name[1][idx] = data,
-                    _ => panic!("invalid nametable write 1"),
-                }
-            } else if self.cart.borrow().mirror == Mirror::HORIZONTAL {
-                match addr {
-                    0x0000..=0x03FF => self.tbl_name[0][idx] = data,
-                    0x0400..=0x07FF => self.tbl_name[0][idx] = data,
-                    0x0800..=0x0BFF => self.tbl_name[1][idx] = data,
-                    0x0C00..=0x0FFF => self.tbl_name[1][idx] = data,
-                    _ => panic!("invalid nametable write 2"),
-                }
-            } else {
-                panic!("invalid mirroring write");
-            }
-        } else if addr >= 0x3F00 && addr <= 0x3FFF {
-            let mut addr = addr & 0x001F;
-            if addr == 0x0010 { addr = 0x0000 };
-            if addr == 0x0014 { addr = 0x0004 };
-            if addr == 0x0018 { addr = 0x0008 };
-            if addr == 0x001C { addr = 0x000C };
-
-            self.tbl_palette[addr as usize] = data;
+        } else if (0x2000..=0x3EFF).contains(&addr) {
+            let (table, idx) = Self::nametable_index(addr, cart.mirror);
+            self.tbl_name[table][idx] = data;
         } else {
-            println!("invalid write to {:X}", addr);
+            self.tbl_palette[Self::palette_index(addr)] = data;
         }
     }
 
+    // (table, offset) of a nametable address after cartridge mirroring
+    fn nametable_index(addr: u16, mirror: Mirror) -> (usize, usize) {
+        let addr = addr & 0x0FFF;
+        let table = match mirror {
+            Mirror::VERTICAL => (addr >> 10) & 0x01,
+            Mirror::HORIZONTAL => (addr >> 11) & 0x01,
+        };
+
+        (table as usize, (addr & 0x03FF) as usize)
+    }
+
+    fn palette_index(addr: u16) -> usize {
+        let mut addr = addr & 0x001F;
+        if addr == 0x0010 { addr = 0x0000 };
+        if addr == 0x0014 { addr = 0x0004 };
+        if addr == 0x0018 { addr = 0x0008 };
+        if addr == 0x001C { addr = 0x000C };
+
+        addr as usize
+    }
+
     pub fn reset(&mut self) {
         self.scroll.write_latch = false;
+        self.frame = 0;
         self.scanline = 0;
         self.cycle = 0;
         self.status = Status::new();
@@ -629,7 +727,7 @@ impl PPU {
     }
 
     pub fn increment_vram_addr(&mut self) {
-        if self.mask.rendering_enabled && (self.scanline == 311 || self.scanline <= 239) {
+        if self.mask.rendering_enabled && (self.scanline == self.prerender_scanline || self.scanline <= 239) {
             self.scroll.increment_x();
             self.scroll.increment_y();
         } else {
@@ -637,7 +735,7 @@ impl PPU {
         }
     }
 
-    pub fn fetch_bg_nt_byte(&mut self) {
+    pub fn fetch_bg_nt_byte(&mut self, cart: &Cartridge) {
         self.prev_palette = self.curr_palette;
         self.curr_palette = self.next_palette;
 
@@ -645,35 +743,35 @@ impl PPU {
         self.tile_shift_hi |= u16::from(self.tile_hi);
 
         let addr = 0x2000 | (self.scroll.addr() & 0x0FFF);
-        let tile_index = u16::from(self.ppu_read(addr));
+        let tile_index = u16::from(self.ppu_read(addr, cart));
 
         self.tile_addr = self.control.bg_select | (tile_index << 4) | self.scroll.fine_y;
     }
 
-    pub fn fetch_bg_attr_byte(&mut self) {
+    pub fn fetch_bg_attr_byte(&mut self, cart: &Cartridge) {
         let addr = self.scroll.attr_addr();
         let shift = self.scroll.attr_shift();
-        self.next_palette = ((self.ppu_read(addr) >> shift) & 0x03) << 2;
+        self.next_palette = ((self.ppu_read(addr, cart) >> shift) & 0x03) << 2;
     }
 
-    pub fn fetch_background(&mut self) {
+    pub fn fetch_background(&mut self, cart: &Cartridge) {
         match self.cycle & 0x07 {
-            1 => self.fetch_bg_nt_byte(),
-            3 => self.fetch_bg_attr_byte(),
-            5 => self.tile_lo = self.ppu_read(self.tile_addr),
-            7 => self.tile_hi = self.ppu_read(self.tile_addr + 8),
+            1 => self.fetch_bg_nt_byte(cart),
+            3 => self.fetch_bg_attr_byte(cart),
+            5 => self.tile_lo = self.ppu_read(self.tile_addr, cart),
+            7 => self.tile_hi = self.ppu_read(self.tile_addr + 8, cart),
             _ => (),
         }
     }
 
-    pub fn tick(&mut self) {
+    pub fn tick(&mut self, cart: &Cartridge) {
         let cycle = self.cycle;
         let scanline = self.scanline;
         let visible_cycle = matches!(cycle, 1..=256);
         let bg_prefetch_cycle = matches!(cycle, 321..=336);
         let bg_fetch_cycle = bg_prefetch_cycle || visible_cycle;
         let visible_scanline = scanline <= 239;
-        let prerender_scanline = self.scanline == 311;
+        let prerender_scanline = self.scanline == self.prerender_scanline;
 
         if self.mask.rendering_enabled {
             let render_scanline = visible_scanline || prerender_scanline;
@@ -682,22 +780,17 @@ impl PPU {
                 let bg_dummy_cycle = matches!(cycle, 337..=340);
 
                 if bg_fetch_cycle {
-                    self.fetch_background();
+                    self.fetch_background(cart);
 
                     if cycle & 0x07 == 0x00 {
                         self.scroll.increment_x();
                     }
                 } else if bg_dummy_cycle {
-                    self.fetch_bg_nt_byte();
+                    self.fetch_bg_nt_byte(cart);
                 }
 
-                if prerender_scanline {
-                    match cycle {
-                        280..=304 => {
-                            self.scroll.copy_y();
-                        },
-                        _ => (),
-                    }
+                if prerender_scanline && matches!(cycle, 280..=304) {
+                    self.scroll.copy_y();
                 }
 
                 match cycle {
@@ -710,11 +803,11 @@ impl PPU {
 
         if self.scroll.delayed_update() {
             let addr = self.scroll.addr();
-            self.ppu_read(addr);
+            self.ppu_read(addr, cart);
         }
 
-        if visible_cycle && visible_cycle { // && !skip_rendering
-            self.render_pixel();
+        if visible_cycle && visible_scanline {
+            self.render_pixel(cycle - 1, cart);
         }
 
         if bg_fetch_cycle {
@@ -733,32 +826,37 @@ impl PPU {
     fn stop_vblank(&mut self) {
         self.status.set_in_vblank(false);
         self.nmi = false;
-        self.open_bus = 0;
     }
 
-    pub fn clock(&mut self) -> usize {
-        let prerender_scanline = 331;
-        let vblank_scanline = 291;
+    pub fn clock(&mut self, cart: &Cartridge) -> usize {
+        self.clock_count = self.clock_count.wrapping_add(1);
 
         if self.cycle >= 340 {
             self.cycle = 0;
             self.scanline += 1;
-            if self.scanline == vblank_scanline - 1 {
-                // this is the post render scanline - do nothing?
-                // self.frame.increment();
-            } else {
-                self.scanline *= (self.scanline <= prerender_scanline) as i32;
+            if self.scanline > self.prerender_scanline {
+                self.scanline = 0;
+                self.frame = self.frame.wrapping_add(1);
             }
         } else {
             self.cycle += 1;
-            self.tick();
+            self.tick(cart);
+
+            // NTSC drops the last pre-render dot on odd frames while rendering
+            let skip_cycle = self.cycle == 339
+                && self.scanline == self.prerender_scanline
+                && self.odd_frame()
+                && self.mask.rendering_enabled
+                && self.region == Region::NTSC;
+            if skip_cycle {
+                self.cycle = 340;
+            }
 
             if self.cycle == 1 {
-                if self.scanline == vblank_scanline {
+                if self.scanline == self.vblank_scanline {
                     self.start_vblank();
-                } else if self.scanline == prerender_scanline {
+                } else if self.scanline == self.prerender_scanline {
                     self.stop_vblank();
-                    self.scanline = -1;
                     self.frame_complete = true;
                 }
             }
@@ -767,34 +865,175 @@ impl PPU {
         1
     }
 
-    fn render_pixel(&mut self) {
-        let x = self.cycle - 1;
+    // The same as calling clock() `dots` times. Nothing the CPU does can land partway
+    // through, so stretches where only the beam moves are skipped in one go and background
+    // tiles are fetched and drawn a whole tile at a time.
+    pub fn run(&mut self, dots: u64, cart: &Cartridge) {
+        // palettes and mask can't change until we're done
+        let colors = self.background_colors();
+        let mut left = dots;
+        while left > 0 {
+            let ran = self.run_batch(left, &colors, cart);
+            if ran == 0 {
+                self.clock(cart);
+                left -= 1;
+            } else {
+                left -= ran;
+            }
+        }
+    }
+
+    // as many dots as can be done at once from here, up to `dots`, or 0 if the next one
+    // needs clock(): the end of a line, the dots vblank starts and ends on, and anything
+    // while a write to v is pending
+    fn run_batch(&mut self, dots: u64, colors: &[u64; 16], cart: &Cartridge) -> u64 {
+        let next = self.cycle + 1;
+        let event = next == 1 && (self.scanline == self.vblank_scanline || self.scanline == self.prerender_scanline);
+        if next > 340 || event || self.scroll.delay_v_cycles > 0 {
+            return 0;
+        }
+
+        let dots = dots.min(341) as i32;
+        let prerender_scanline = self.scanline == self.prerender_scanline;
+        let ran = if self.mask.rendering_enabled && (self.scanline <= 239 || prerender_scanline) {
+            if next & 0x07 == 1 && dots >= 8 && (next <= 249 || matches!(next, 321..=329)) {
+                // as many whole tiles as there are dots for, up to the end of this run of fetches
+                let end = if next <= 249 { 256 } else { 336 };
+                let mut tile = next;
+                while tile + 7 <= end.min(self.cycle + dots) {
+                    self.fetch_tile(tile, colors, cart);
+                    tile += 8;
+                }
+                tile - next
+            } else if matches!(next, 258..=320) {
+                // between the sprite and the next line's tile fetches, only the vertical
+                // scroll copy on the pre-render line does anything
+                let last = (self.cycle + dots).min(320);
+                if prerender_scanline && next <= 304 && last >= 280 {
+                    self.scroll.copy_y();
+                }
+                last - self.cycle
+            } else {
+                0
+            }
+        } else {
+            let last = (self.cycle + dots).min(340);
+            self.idle(next, last, cart);
+            last - self.cycle
+        };
+
+        self.cycle += ran;
+        self.clock_count = self.clock_count.wrapping_add(ran as u64);
+        ran as u64
+    }
+
+    // dots `next` to `next + 7` of a rendering line, which start on a tile boundary
+    fn fetch_tile(&mut self, next: i32, colors: &[u64; 16], cart: &Cartridge) {
+        self.fetch_bg_nt_byte(cart);
+
+        if self.scanline <= 239 && next <= 256 {
+            self.draw_tile(next - 1, colors);
+        }
+        self.tile_shift_lo <<= 8;
+        self.tile_shift_hi <<= 8;
+
+        self.fetch_bg_attr_byte(cart);
+        self.tile_lo = self.ppu_read(self.tile_addr, cart);
+        self.tile_hi = self.ppu_read(self.tile_addr + 8, cart);
+
+        self.scroll.increment_x();
+        if next + 7 == 256 {
+            self.scroll.increment_y();
+        }
+    }
+
+    // RGB for each background palette entry as render_pixel() would put it out with
+    // rendering on, so color 0 of every palette is the backdrop at $3F00. Packed into the
+    // low three bytes so draw_tile() can lay pixels out a word at a time.
+    fn background_colors(&self) -> [u64; 16] {
+        std::array::from_fn(|i| {
+            let color = if i & 0x03 > 0 { self.tbl_palette[i] } else { self.tbl_palette[0] };
+            let [r, g, b] = SYSTEM_PALETTE[(u16::from(color & self.mask.grayscale) | self.mask.emphasis) as usize & 0x3F];
+            u64::from_le_bytes([r, g, b, 0, 0, 0, 0, 0])
+        })
+    }
+
+    // render_pixel() for the eight pixels from x, with rendering on
+    fn draw_tile(&mut self, x: i32, colors: &[u64; 16]) {
+        let fine_x = self.scroll.fine_x;
+        // tiles start on multiples of 8, so the left clip takes all of the first one or none
+        let shown = self.mask.show_bg && (x >= 8 || self.mask.show_left_bg);
+        let (lo, hi) = if shown { (self.tile_shift_lo << fine_x, self.tile_shift_hi << fine_x) } else { (0, 0) };
+
+        // palette entry of each pixel, one per byte: the first 8 - fine_x pixels are still
+        // from the previous tile
+        let prev = 1u64.checked_shl(u32::from(8 - fine_x) * 8).unwrap_or(0).wrapping_sub(1);
+        let palettes = ((u64::from(self.prev_palette) * 0x0101_0101_0101_0101) & prev)
+            | ((u64::from(self.curr_palette) * 0x0101_0101_0101_0101) & !prev);
+        let entries = palettes | PIXEL_BITS[usize::from(hi >> 8)] << 1 | PIXEL_BITS[usize::from(lo >> 8)];
+        let p: [u64; 8] = std::array::from_fn(|i| colors[(entries >> (i * 8)) as usize & 0x0F]);
+
+        // 8 pixels of 3 bytes are 3 words
+        let words = [
+            p[0] | p[1] << 24 | p[2] << 48,
+            p[2] >> 16 | p[3] << 8 | p[4] << 32 | p[5] << 56,
+            p[5] >> 8 | p[6] << 16 | p[7] << 40,
+        ];
+        let start = (self.scanline as usize * SCREEN_WIDTH + x as usize) * 3;
+        for (bytes, word) in self.screen[start..start + 8 * 3].chunks_exact_mut(8).zip(words) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    // dots `next` to `last` of a line where nothing is fetched: the shifters still shift,
+    // and with rendering off visible lines show the one backdrop color
+    fn idle(&mut self, next: i32, last: i32, cart: &Cartridge) {
+        let dots_within = |from: i32, to: i32| (last.min(to) - next.max(from) + 1).max(0) as u32;
+
+        let shifts = dots_within(1, 256) + dots_within(321, 336);
+        self.tile_shift_lo = self.tile_shift_lo.checked_shl(shifts).unwrap_or(0);
+        self.tile_shift_hi = self.tile_shift_hi.checked_shl(shifts).unwrap_or(0);
+
+        let pixels = dots_within(1, 256) as usize;
+        if self.scanline <= 239 && pixels > 0 {
+            let x = next - 1;
+            self.render_pixel(x, cart);
+            let start = (self.scanline as usize * SCREEN_WIDTH + x as usize) * 3;
+            let (rgb, rest) = self.screen[start..start + pixels * 3].split_at_mut(3);
+            for pixel in rest.chunks_exact_mut(3) {
+                pixel.copy_from_slice(rgb);
+            }
+        }
+    }
+
+    fn render_pixel(&mut self, x: i32, cart: &Cartridge) {
         let y = self.scanline;
         let addr = self.scroll.addr();
 
         let color = if self.mask.rendering_enabled || (addr & 0x3F00) != 0x3F00 {
-            let color = u16::from(self.pixel_color());
-            self.ppu_read(0x3F00 + (color & 0x03 > 0) as u16 * color)
+            let color = u16::from(self.pixel_color(x));
+            self.ppu_read(0x3F00 + (color & 0x03 > 0) as u16 * color, cart)
         } else {
-            self.ppu_read(addr)
+            self.ppu_read(addr, cart)
         };
 
-        let pixel = self.pal_screen[(u16::from(color & self.mask.grayscale) | self.mask.emphasis) as usize & 0x3F];
-        self.spr_screen.set_pixel(x, y, pixel);
+        let rgb = SYSTEM_PALETTE[(u16::from(color & self.mask.grayscale) | self.mask.emphasis) as usize & 0x3F];
+        let offset = (y as usize * SCREEN_WIDTH + x as usize) * 3;
+        self.screen[offset..offset + 3].copy_from_slice(&rgb);
     }
 
-    fn pixel_color(&self) -> u8 {
-        let x = self.cycle - 1;
+    fn pixel_color(&self, x: i32) -> u8 {
+        let fine_x = self.scroll.fine_x;
 
         let left_clip_bg = x < 8 && !self.mask.show_left_bg;
         let bg_color = if self.mask.show_bg && !left_clip_bg {
-            ((((self.tile_shift_hi << self.fine_x) & 0x8000) >> 14)
-                | (((self.tile_shift_lo << self.fine_x) & 0x8000) >> 15)) as u8
+            ((((self.tile_shift_hi << fine_x) & 0x8000) >> 14)
+                | (((self.tile_shift_lo << fine_x) & 0x8000) >> 15)) as u8
         } else {
             0
         };
 
-        if (u16::from(self.fine_x) + ((x & 0x07) as u16)) < 8 {
+        if (fine_x + ((x & 0x07) as u16)) < 8 {
             self.prev_palette + bg_color
         } else {
             self.curr_palette + bg_color
@@ -802,21 +1041,32 @@ impl PPU {
     }
 
     fn peek_status(&self) -> u8 {
-        (self.status.read() & 0xE0) | (self.open_bus & 0x1F)
+        (self.status.read() & 0xE0) | (self.open_bus.read(self.clock_count) & 0x1F)
+    }
+
+    fn peek_oam(&self) -> u8 {
+        let data = self.oam[self.oam_addr as usize];
+        // bits 2-4 of the sprite attribute byte don't exist
+        if self.oam_addr & 0x03 == 0x02 {
+            data & 0xE3
+        } else {
+            data
+        }
     }
 
-    pub fn get_pattern_table(&self, i: u8) -> &olc::Sprite {
-        &self.spr_pattern_table[i as usize]
+    // 128x128 RGB
+    pub fn get_pattern_table(&self, i: u8) -> &[u8] {
+        &self.pattern_tables[i as usize]
     }
 
-    pub fn build_pattern_table(&mut self, i: u8, palette: u8) {
+    pub fn build_pattern_table(&mut self, i: u8, palette: u8, cart: &Cartridge) {
         for tile_y in 0..16 {
             for tile_x in 0..16 {
                 let offset = (tile_y * 256) + (tile_x * 16);
 
                 for row in 0..8 {
-                    let mut tile_lsb = self.ppu_read(u16::from(i) * 0x1000 + offset + row);
-                    let mut tile_msb = self.ppu_read(u16::from(i) * 0x1000 + offset + row + 8);
+                    let mut tile_lsb = self.ppu_read(u16::from(i) * 0x1000 + offset + row, cart);
+                    let mut tile_msb = self.ppu_read(u16::from(i) * 0x1000 + offset + row + 8, cart);
 
                     for col in 0..8 {
                         let pixel = (tile_lsb & 0x01) + (tile_msb & 0x01);
@@ -824,19 +1074,19 @@ impl PPU {
                         tile_lsb >>= 1;
                         tile_msb >>= 1;
 
-                        self.spr_pattern_table[i as usize].set_pixel(
-                            (tile_x * 8 + (7 - col)).into(),
-                            (tile_y * 8 + row).into(),
-                            self.get_color_from_palette_ram(palette, pixel)
-                        );
+                        let rgb = self.get_color_from_palette_ram(palette, pixel, cart);
+                        let x = usize::from(tile_x * 8 + (7 - col));
+                        let y = usize::from(tile_y * 8 + row);
+                        let offset = (y * 128 + x) * 3;
+                        self.pattern_tables[i as usize][offset..offset + 3].copy_from_slice(&rgb);
                     }
                 }
             }
         }
     }
 
-    pub fn get_color_from_palette_ram(&self, palette: u8, pixel: u8) -> olc::Pixel {
-        let idx = self.ppu_read(0x3F00 + (u16::from(palette) * 4) + u16::from(pixel));
-        self.pal_screen[idx as usize & 0x3F]
+    pub fn get_color_from_palette_ram(&self, palette: u8, pixel: u8, cart: &Cartridge) -> [u8; 3] {
+        let idx = self.ppu_read(0x3F00 + (u16::from(palette) * 4) + u16::from(pixel), cart);
+        SYSTEM_PALETTE[idx as usize & 0x3F]
     }
 }
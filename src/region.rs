@@ -0,0 +1,63 @@
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    NTSC,
+    PAL,
+    DENDY,
+}
+
+impl Region {
+    pub const fn master_clock_hz(&self) -> u64 {
+        match self {
+            Region::NTSC => 21_477_272,
+            Region::PAL | Region::DENDY => 26_601_712,
+        }
+    }
+
+    // master clocks per CPU cycle
+    pub const fn cpu_divider(&self) -> u64 {
+        match self {
+            Region::NTSC => 12,
+            Region::PAL => 16,
+            Region::DENDY => 15,
+        }
+    }
+
+    // master clocks per PPU dot
+    pub const fn ppu_divider(&self) -> u64 {
+        match self {
+            Region::NTSC => 4,
+            Region::PAL | Region::DENDY => 5,
+        }
+    }
+
+    pub const fn vblank_scanline(&self) -> i32 {
+        match self {
+            Region::NTSC | Region::PAL => 241,
+            // Dendy keeps 51 post-render lines so vblank lines up with NTSC timing
+            Region::DENDY => 291,
+        }
+    }
+
+    pub const fn prerender_scanline(&self) -> i32 {
+        match self {
+            Region::NTSC => 261,
+            Region::PAL | Region::DENDY => 311,
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::NTSC),
+            "pal" => Ok(Region::PAL),
+            "dendy" => Ok(Region::DENDY),
+            x => Err(format!("unknown region {x}")),
+        }
+    }
+}
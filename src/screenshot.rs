@@ -0,0 +1,172 @@
+// Golden-image testing for the PPU: run a ROM with scripted input, grab the screen, and
+// compare it with a PNG checked in next to the tests. Set BLESS=1 to (re)write the goldens
+// from what the emulator draws now:
+//
+//   BLESS=1 cargo test --test screenshots
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::nes::Nes;
+use crate::ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const BLESS_VAR: &str = "BLESS";
+
+// controller 1 holds these buttons from the given frame on (bit 7 A ... bit 0 Right, as
+// read from $4016)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Input {
+    pub frame: u32,
+    pub buttons: u8,
+}
+
+// 8-bit RGB, row major
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn from_ppu(ppu: &PPU) -> Frame {
+        Frame {
+            width: SCREEN_WIDTH as u32,
+            height: SCREEN_HEIGHT as u32,
+            pixels: ppu.screen.clone(),
+        }
+    }
+
+    // FNV-1a, enough to tell frames apart without keeping the image
+    pub fn hash(&self) -> u64 {
+        self.pixels.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn load_png(path: &Path) -> Result<Frame, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut decoder = png::Decoder::new(file);
+        // palette and 16-bit images come out as 8-bit RGB(A)
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| format!("{}: {e}", path.display()))?;
+        buf.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgb => buf,
+            png::ColorType::Rgba => buf.chunks(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks(2).flat_map(|p| [p[0], p[0], p[0]]).collect(),
+            png::ColorType::Indexed => return Err(format!("{}: unexpanded palette", path.display())),
+        };
+
+        Ok(Frame {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    // Pixels that differ from other in red, the rest faded to gray so the red stands out.
+    // None if the frames match; frames of different sizes can't be diffed and are all red.
+    pub fn diff(&self, other: &Frame) -> Option<(usize, Frame)> {
+        if self.width != other.width || self.height != other.height {
+            let pixels = [0xFF, 0x00, 0x00].repeat((self.width * self.height) as usize);
+            let diff = Frame { pixels, ..self.clone() };
+            return Some(((self.width * self.height) as usize, diff));
+        }
+
+        let mut count = 0;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for (a, b) in self.pixels.chunks(3).zip(other.pixels.chunks(3)) {
+            if a == b {
+                let gray = ((u16::from(a[0]) + u16::from(a[1]) + u16::from(a[2])) / 6) as u8;
+                pixels.extend([gray, gray, gray]);
+            } else {
+                count += 1;
+                pixels.extend([0xFF, 0x00, 0x00]);
+            }
+        }
+
+        if count == 0 {
+            None
+        } else {
+            Some((count, Frame { pixels, ..self.clone() }))
+        }
+    }
+}
+
+// Run for the given number of frames, applying the input script as it goes, and return the
+// last frame drawn.
+pub fn capture(nes: &mut Nes, frames: u32, script: &[Input]) -> Frame {
+    for frame in 0..frames {
+        if let Some(input) = script.iter().rev().find(|input| input.frame <= frame) {
+            if let Some(pad) = nes.cpu.bus.pad_mut(0) {
+                pad.buttons = input.buttons;
+            }
+        }
+        nes.run_frame();
+    }
+    Frame::from_ppu(nes.ppu())
+}
+
+// Compare with the golden image, or write it when blessing. On a mismatch the actual frame
+// and a diff image are written next to the golden as <name>.actual.png and <name>.diff.png.
+pub fn check_golden(frame: &Frame, golden: &Path) -> Result<(), String> {
+    if std::env::var_os(BLESS_VAR).is_some() {
+        if let Some(dir) = golden.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        }
+        return frame.save_png(golden);
+    }
+
+    let actual_path = sibling(golden, "actual");
+    let diff_path = sibling(golden, "diff");
+    if !golden.exists() {
+        frame.save_png(&actual_path)?;
+        return Err(format!(
+            "no golden image at {}, wrote {}; run with {BLESS_VAR}=1 to accept it",
+            golden.display(),
+            actual_path.display()
+        ));
+    }
+
+    let expected = Frame::load_png(golden)?;
+    match frame.diff(&expected) {
+        None => {
+            // clear out whatever an earlier failure left behind
+            let _ = fs::remove_file(&actual_path);
+            let _ = fs::remove_file(&diff_path);
+            Ok(())
+        },
+        Some((count, diff)) => {
+            frame.save_png(&actual_path)?;
+            diff.save_png(&diff_path)?;
+            Err(format!(
+                "{count} pixels differ from {} (hash {:016X}, expected {:016X}); see {}",
+                golden.display(),
+                frame.hash(),
+                expected.hash(),
+                diff_path.display()
+            ))
+        },
+    }
+}
+
+fn sibling(golden: &Path, suffix: &str) -> PathBuf {
+    let stem = golden.file_stem().unwrap_or_default().to_string_lossy();
+    golden.with_file_name(format!("{stem}.{suffix}.png"))
+}
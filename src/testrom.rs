@@ -0,0 +1,87 @@
+// Runs test ROMs that report through cartridge RAM, as blargg's do:
+//
+//   $6000        status: $80 running, $81 wants the reset button pressed, otherwise the
+//                result code, 0 for a pass
+//   $6001-$6003  DE B0 61 once the status is valid
+//   $6004        the message, zero terminated
+use crate::bus::CpuBus;
+use crate::cartridge::Cartridge;
+use crate::nes::Nes;
+
+const STATUS: u16 = 0x6000;
+const SIGNATURE: u16 = 0x6001;
+const MESSAGE: u16 = 0x6004;
+
+const SIGNATURE_BYTES: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const RUNNING: u8 = 0x80;
+const RESET: u8 = 0x81;
+
+// the ROMs want the button held off for at least 100ms after asking
+const RESET_DELAY_FRAMES: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomResult {
+    pub code: u8,
+    pub message: String,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+// Run until the ROM reports a result, giving up after max_frames frames.
+pub fn run_test_rom(rom: &[u8], max_frames: u32) -> Result<TestRomResult, String> {
+    let mut nes = Nes::new(Cartridge::parse(rom)?);
+    nes.reset();
+
+    let mut reset_at = None;
+    for frame in 0..max_frames {
+        nes.run_frame();
+
+        if nes.cpu.halted() {
+            return Err(format!("CPU stopped at {:04X} after {frame} frames", nes.cpu.pc));
+        }
+        if !has_signature(&mut nes) {
+            continue;
+        }
+
+        match nes.cpu.bus.peek(STATUS) {
+            RUNNING => {},
+            RESET => {
+                let at = *reset_at.get_or_insert(frame + RESET_DELAY_FRAMES);
+                if frame >= at {
+                    // the ROM writes $80 again once it's back up
+                    nes.cpu.bus.write(STATUS, RUNNING);
+                    nes.reset();
+                    reset_at = None;
+                }
+            },
+            code => {
+                return Ok(TestRomResult {
+                    code,
+                    message: message(&mut nes),
+                });
+            },
+        }
+    }
+
+    Err(format!("no result after {max_frames} frames, {:?}", message(&mut nes)))
+}
+
+fn has_signature(nes: &mut Nes) -> bool {
+    (0..3).all(|i| nes.cpu.bus.peek(SIGNATURE + i) == SIGNATURE_BYTES[i as usize])
+}
+
+fn message(nes: &mut Nes) -> String {
+    let mut text = Vec::new();
+    for addr in MESSAGE..0x8000 {
+        match nes.cpu.bus.peek(addr) {
+            0 => break,
+            byte => text.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&text).trim_end().to_string()
+}
@@ -0,0 +1,119 @@
+// blargg's test ROMs report through $6000. They aren't redistributed here, so test_roms is
+// ignored by default: drop the .nes files from https://github.com/christopherpow/nes-test-roms
+// (any directory layout) in tests/data/blargg/ and run
+// `cargo test --test blargg -- --ignored`. ROMs for mappers we don't have yet are skipped.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rs6502::cartridge::Cartridge;
+use rs6502::testrom::run_test_rom;
+
+const ROM_DIR: &str = "tests/data/blargg";
+
+// the slowest of the suites take around 30 seconds of emulated time
+const MAX_FRAMES: u32 = 60 * 60;
+
+// NROM image following the protocol: sets the signature, asks for a reset, and once reset
+// copies the message and reports the code
+fn protocol_rom(code: u8, message: &str) -> Vec<u8> {
+    #[rustfmt::skip]
+    let program = [
+        0xAD, 0x10, 0x60,       // 8000  LDA $6010    boot count, kept across the reset
+        0xD0, 0x1F,             // 8003  BNE $8024
+        0xEE, 0x10, 0x60,       // 8005  INC $6010
+        0xA9, 0x80, 0x8D, 0x00, 0x60, // 8008  status: running
+        0xA9, 0xDE, 0x8D, 0x01, 0x60, // 800D  signature
+        0xA9, 0xB0, 0x8D, 0x02, 0x60, // 8012
+        0xA9, 0x61, 0x8D, 0x03, 0x60, // 8017
+        0xA9, 0x81, 0x8D, 0x00, 0x60, // 801C  status: press reset
+        0x4C, 0x21, 0x80,       // 8021  JMP $8021
+        0xA9, 0x80, 0x8D, 0x00, 0x60, // 8024  status: running
+        0xA2, 0x00,             // 8029  LDX #$00
+        0xBD, 0x00, 0x81,       // 802B  LDA $8100,X
+        0x9D, 0x04, 0x60,       // 802E  STA $6004,X
+        0xF0, 0x04,             // 8031  BEQ $8037
+        0xE8,                   // 8033  INX
+        0x4C, 0x2B, 0x80,       // 8034  JMP $802B
+        0xA9, code, 0x8D, 0x00, 0x60, // 8037  status: result
+        0x4C, 0x3C, 0x80,       // 803C  JMP $803C
+    ];
+
+    let mut prg = vec![0; 0x4000];
+    prg[..program.len()].copy_from_slice(&program);
+    prg[0x0100..0x0100 + message.len()].copy_from_slice(message.as_bytes());
+    prg[0x3FFA..].copy_from_slice(&[0x3C, 0x80, 0x00, 0x80, 0x3C, 0x80]);
+
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+    rom
+}
+
+#[test]
+fn reports_a_pass_after_the_requested_reset() {
+    let result = run_test_rom(&protocol_rom(0x00, "\nPassed\n"), 60).unwrap();
+    assert!(result.passed());
+    assert_eq!(result.message, "\nPassed");
+}
+
+#[test]
+fn reports_the_failure_code_and_message() {
+    let result = run_test_rom(&protocol_rom(0x03, "Failed #3"), 60).unwrap();
+    assert!(!result.passed());
+    assert_eq!(result.code, 0x03);
+    assert_eq!(result.message, "Failed #3");
+}
+
+#[test]
+fn gives_up_without_a_signature() {
+    let mut rom = protocol_rom(0x00, "");
+    // jump straight to the spin loop
+    rom[16..19].copy_from_slice(&[0x4C, 0x3C, 0x80]);
+    assert!(run_test_rom(&rom, 10).is_err());
+}
+
+#[test]
+fn unsupported_mappers_are_an_error() {
+    let mut rom = protocol_rom(0x00, "");
+    rom[6] = 0x10;
+    assert_eq!(run_test_rom(&rom, 10), Err("unsupported mapper ID 1".to_string()));
+}
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes")) {
+            roms.push(path);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs ROMs in tests/data/blargg/"]
+fn test_roms() {
+    let mut roms = Vec::new();
+    find_roms(Path::new(ROM_DIR), &mut roms);
+    assert!(!roms.is_empty(), "no ROMs in {ROM_DIR}");
+    roms.sort();
+
+    let mut failures = Vec::new();
+    for path in roms {
+        let rom = fs::read(&path).unwrap();
+        if let Err(e) = Cartridge::parse(&rom) {
+            eprintln!("skipping {}: {e}", path.display());
+            continue;
+        }
+
+        match run_test_rom(&rom, MAX_FRAMES) {
+            Ok(result) if result.passed() => {},
+            Ok(result) => failures.push(format!("{}: #{} {}", path.display(), result.code, result.message)),
+            Err(e) => failures.push(format!("{}: {e}", path.display())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{} failed:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{cpu_with_program, step, PROGRAM_START};
+use rs6502::bus::CpuBus;
+use rs6502::cartridge::Cartridge;
+use rs6502::cpu::CPU;
+use rs6502::nes::Nes;
+use rs6502::region::Region;
+
+fn dots(cpu: &mut CPU) -> i32 {
+    let (scanline, dot) = cpu.bus.ppu_position();
+    scanline * 341 + dot
+}
+
+#[test]
+fn the_ppu_keeps_pace_with_every_cycle() {
+    // LDA $0300 / NOP / INC $10
+    let mut cpu = cpu_with_program(&[0xAD, 0x00, 0x03, 0xEA, 0xE6, 0x10]);
+    let start = dots(&mut cpu);
+    assert_eq!(start, 21);
+
+    for expected in [4, 2, 5] {
+        let before = dots(&mut cpu);
+        assert_eq!(step(&mut cpu), expected);
+        assert_eq!(dots(&mut cpu) - before, expected as i32 * 3);
+    }
+}
+
+#[test]
+fn register_reads_see_the_ppu_at_their_own_cycle() {
+    // wait for vblank: BIT $2002 / BPL -5
+    let mut cpu = cpu_with_program(&[0x2C, 0x02, 0x20, 0x10, 0xFB]);
+    while cpu.pc != PROGRAM_START + 5 {
+        step(&mut cpu);
+    }
+
+    // the flag goes up at dot 1 of line 241; the read that saw it was the last cycle of
+    // BIT, so only the rest of that cycle and the 2 of BPL have gone by since
+    let (scanline, dot) = cpu.bus.ppu_position();
+    assert_eq!(scanline, 241);
+    assert!((1..=1 + 9).contains(&dot), "dot {dot}");
+}
+
+#[test]
+fn the_ppu_only_runs_when_something_needs_it() {
+    // NOP / JMP $0200
+    let mut cpu = cpu_with_program(&[0xEA, 0x4C, 0x00, 0x02]);
+    for _ in 0..10 {
+        step(&mut cpu);
+    }
+    assert!(cpu.bus.ppu_clock < cpu.bus.master_clock);
+
+    // 7 reset cycles and 5 each time round the loop
+    assert_eq!(dots(&mut cpu), (7 + 5 * 5) * 3);
+    assert!(cpu.bus.master_clock - cpu.bus.ppu_clock < 4);
+}
+
+#[test]
+fn each_region_runs_the_ppu_at_its_own_ratio() {
+    // PPU dots per 5 CPU cycles: 3:1 on NTSC and Dendy, 3.2:1 on PAL
+    for (region, dots_per_5_cycles) in [(Region::NTSC, 15), (Region::PAL, 16), (Region::DENDY, 15)] {
+        let mut nes = Nes::new(Cartridge::new("nestest.nes".to_string()).unwrap());
+        nes.set_region(region);
+        nes.reset();
+
+        let start = dots(&mut nes.cpu);
+        let mut cycles = 0;
+        while cycles < 1000 {
+            cycles += step(&mut nes.cpu);
+        }
+        let expected = cycles as i32 * dots_per_5_cycles / 5;
+        let ran = dots(&mut nes.cpu) - start;
+        assert!((ran - expected).abs() <= 1, "{region:?}: {ran} dots in {cycles} cycles");
+    }
+}
+
+#[test]
+fn region_dividers_match_the_crystals() {
+    // CPU and PPU clocks in Hz, from the master clock
+    let clocks = |region: Region| {
+        (region.master_clock_hz() / region.cpu_divider(), region.master_clock_hz() / region.ppu_divider())
+    };
+    assert_eq!(clocks(Region::NTSC), (1_789_772, 5_369_318));
+    assert_eq!(clocks(Region::PAL), (1_662_607, 5_320_342));
+    assert_eq!(clocks(Region::DENDY), (1_773_447, 5_320_342));
+}
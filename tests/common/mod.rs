@@ -0,0 +1,64 @@
+// not every test binary uses every helper
+#![allow(dead_code)]
+
+use rs6502::bus::{Bus, CpuBus};
+use rs6502::cartridge::Cartridge;
+use rs6502::cpu::{State, CPU};
+use rs6502::ppu::PPU;
+
+pub const PROGRAM_START: u16 = 0x0200;
+
+// nestest in automation mode: straight to $C000, the PPU already 7 reset cycles along
+pub fn nestest_cpu() -> CPU {
+    let cartridge = Cartridge::new("nestest.nes".to_string()).unwrap();
+    let ppu = PPU::new(cartridge.region);
+    let bus = Bus::new(cartridge, ppu);
+
+    let mut cpu = CPU::new(bus);
+    cpu.reset();
+    cpu.pc = 0xC000;
+    cpu
+}
+
+// NROM cart whose reset vector points into RAM, where the program under test lives
+pub fn cpu_with_program(program: &[u8]) -> CPU {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0; 0x4000];
+    prg[0x3FFC..].copy_from_slice(&[0x00, 0x02, 0x00, 0x00]);
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+
+    let cartridge = Cartridge::parse(&rom).unwrap();
+    let ppu = PPU::new(cartridge.region);
+    let bus = Bus::new(cartridge, ppu);
+
+    let mut cpu = CPU::new(bus);
+    for (i, byte) in program.iter().enumerate() {
+        cpu.bus.cpu_write(PROGRAM_START + i as u16, *byte);
+    }
+    cpu.reset();
+    cpu
+}
+
+// run one instruction and return how many cycles it took, 0 if the CPU is stopped
+pub fn step<B: CpuBus>(cpu: &mut CPU<B>) -> usize {
+    loop {
+        let cycles = cpu.clock();
+        if cycles > 0 || cpu.state != State::Running {
+            return cycles;
+        }
+    }
+}
+
+// run until the program jumps or branches to itself, or stops the CPU, and return where it
+// got stuck
+pub fn run_to_trap<B: CpuBus>(cpu: &mut CPU<B>, max_instructions: usize) -> Option<u16> {
+    for _ in 0..max_instructions {
+        let pc = cpu.pc;
+        step(cpu);
+        if cpu.pc == pc || cpu.state != State::Running {
+            return Some(cpu.pc);
+        }
+    }
+    None
+}
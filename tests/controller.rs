@@ -0,0 +1,203 @@
+mod common;
+
+use common::{cpu_with_program, step, PROGRAM_START};
+use rs6502::bus::Bus;
+use rs6502::cpu::CPU;
+use rs6502::cartridge::Cartridge;
+use rs6502::input::{Controller, Famicom4P, FourScore, InputDevice, InputType};
+use rs6502::nes::Nes;
+use rs6502::ppu::PPU;
+
+const A: u8 = Controller::A;
+const RIGHT: u8 = Controller::RIGHT;
+
+fn pad(cpu: &mut CPU, port: usize) -> &mut Controller {
+    cpu.bus.device_mut::<Controller>(port).unwrap()
+}
+
+fn strobe(cpu: &mut CPU) {
+    cpu.bus.cpu_write(0x4016, 0x01);
+    cpu.bus.cpu_write(0x4016, 0x00);
+}
+
+fn bits(bus: &mut Bus, addr: u16, count: usize) -> Vec<u8> {
+    (0..count).map(|_| bus.cpu_read(addr, false) & 0x01).collect()
+}
+
+#[test]
+fn buttons_shift_out_a_first_then_ones() {
+    let mut cpu = cpu_with_program(&[]);
+    pad(&mut cpu, 0).buttons = A | RIGHT;
+    strobe(&mut cpu);
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 10), [1, 0, 0, 0, 0, 0, 0, 1, 1, 1]);
+}
+
+#[test]
+fn a_held_strobe_keeps_reading_a_as_it_is_now() {
+    let mut cpu = cpu_with_program(&[]);
+    pad(&mut cpu, 0).buttons = A;
+    cpu.bus.cpu_write(0x4016, 0x01);
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 3), [1, 1, 1]);
+
+    pad(&mut cpu, 0).buttons = RIGHT;
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 1), [0]);
+
+    // the falling edge latches what's held at that point
+    cpu.bus.cpu_write(0x4016, 0x00);
+    pad(&mut cpu, 0).buttons = 0;
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 8), [0, 0, 0, 0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn writing_zero_again_does_not_reload() {
+    let mut cpu = cpu_with_program(&[]);
+    pad(&mut cpu, 0).buttons = A | RIGHT;
+    strobe(&mut cpu);
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 2), [1, 0]);
+    cpu.bus.cpu_write(0x4016, 0x00);
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 6), [0, 0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn both_ports_are_strobed_through_4016() {
+    let mut cpu = cpu_with_program(&[]);
+    pad(&mut cpu, 1).buttons = A;
+
+    // $4017 writes go to the APU frame counter, not the pads
+    cpu.bus.cpu_write(0x4017, 0x01);
+    cpu.bus.cpu_write(0x4017, 0x00);
+    assert_eq!(bits(&mut cpu.bus, 0x4017, 1), [0]);
+
+    strobe(&mut cpu);
+    assert_eq!(bits(&mut cpu.bus, 0x4017, 2), [1, 0]);
+}
+
+#[test]
+fn peeking_does_not_shift() {
+    let mut cpu = cpu_with_program(&[]);
+    pad(&mut cpu, 0).buttons = A;
+    strobe(&mut cpu);
+    assert_eq!(cpu.bus.cpu_read(0x4016, true) & 0x01, 1);
+    assert_eq!(cpu.bus.cpu_read(0x4016, true) & 0x01, 1);
+    assert_eq!(bits(&mut cpu.bus, 0x4016, 2), [1, 0]);
+}
+
+#[test]
+fn upper_bits_are_open_bus() {
+    // LDA $4016: the last thing on the bus before the read is the $40 of the address
+    let mut cpu = cpu_with_program(&[0xAD, 0x16, 0x40]);
+    pad(&mut cpu, 0).buttons = A;
+    strobe(&mut cpu);
+    step(&mut cpu);
+    assert_eq!(cpu.pc, PROGRAM_START + 3);
+    assert_eq!(cpu.a, 0x41);
+}
+
+// counts strobes and answers with a fixed pattern
+#[derive(Clone)]
+struct Probe {
+    strobes: usize,
+    data: u8,
+}
+
+impl InputDevice for Probe {
+    fn strobe(&mut self, on: bool) {
+        if on {
+            self.strobes += 1;
+        }
+    }
+
+    fn read(&mut self, _ppu: &PPU, _read_only: bool) -> u8 {
+        self.data
+    }
+}
+
+#[test]
+fn an_empty_port_reads_nothing_but_open_bus() {
+    let mut cpu = cpu_with_program(&[]);
+    assert!(cpu.bus.unplug(1).is_some());
+    cpu.bus.open_bus = 0xFF;
+    assert_eq!(cpu.bus.cpu_read(0x4017, false), 0xE0);
+}
+
+#[test]
+fn any_device_can_be_plugged_in() {
+    let mut cpu = cpu_with_program(&[]);
+    let old = cpu.bus.plug(1, Box::new(Probe { strobes: 0, data: 0xFF }));
+    assert!(old.is_some());
+    assert!(cpu.bus.device_mut::<Controller>(1).is_none());
+
+    strobe(&mut cpu);
+    cpu.bus.open_bus = 0x00;
+    // only D0-D4 reach the CPU
+    assert_eq!(cpu.bus.cpu_read(0x4017, false), 0x1F);
+    assert_eq!(cpu.bus.device_mut::<Probe>(1).unwrap().strobes, 1);
+
+    // and the machine still clones, device and all
+    let mut copy = cpu.bus.clone();
+    copy.device_mut::<Probe>(1).unwrap().strobes = 5;
+    assert_eq!(cpu.bus.device_mut::<Probe>(1).unwrap().strobes, 1);
+}
+
+fn bytes(bus: &mut Bus, addr: u16, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|_| (0..8).fold(0, |byte, _| (byte << 1) | (bus.cpu_read(addr, false) & 0x01)))
+        .collect()
+}
+
+#[test]
+fn the_four_score_sends_both_pads_then_its_signature() {
+    let mut cpu = cpu_with_program(&[]);
+    cpu.bus.set_input(InputType::FOURSCORE);
+    for (pad, buttons) in [A, RIGHT, Controller::START, Controller::B].into_iter().enumerate() {
+        cpu.bus.pad_mut(pad).unwrap().buttons = buttons;
+    }
+
+    strobe(&mut cpu);
+    assert_eq!(bytes(&mut cpu.bus, 0x4016, 4), [A, Controller::START, 0x10, 0xFF]);
+    assert_eq!(bytes(&mut cpu.bus, 0x4017, 4), [RIGHT, Controller::B, 0x20, 0xFF]);
+}
+
+#[test]
+fn the_famicom_adapter_puts_pads_3_and_4_on_d1() {
+    let mut cpu = cpu_with_program(&[]);
+    cpu.bus.set_input(InputType::FAMICOM4P);
+    cpu.bus.pad_mut(0).unwrap().buttons = A;
+    cpu.bus.pad_mut(2).unwrap().buttons = Controller::B;
+    cpu.bus.pad_mut(3).unwrap().buttons = A;
+
+    strobe(&mut cpu);
+    let reads: Vec<_> = (0..3).map(|_| cpu.bus.cpu_read(0x4016, false) & 0x03).collect();
+    assert_eq!(reads, [0x01, 0x02, 0x00]);
+    assert_eq!(cpu.bus.cpu_read(0x4017, false) & 0x03, 0x02);
+    assert!(cpu.bus.device_mut::<Famicom4P>(1).is_some());
+}
+
+#[test]
+fn plain_pads_only_have_pads_1_and_2() {
+    let mut cpu = cpu_with_program(&[]);
+    assert!(cpu.bus.pad_mut(1).is_some());
+    assert!(cpu.bus.pad_mut(2).is_none());
+}
+
+#[test]
+fn the_rom_header_picks_the_adapter() {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0x08, 0, 0, 0, 0, 0, 0, 0, 0x02];
+    rom.extend(vec![0; 0x4000 + 0x2000]);
+    let cartridge = Cartridge::parse(&rom).unwrap();
+    assert_eq!(cartridge.input, InputType::FOURSCORE);
+
+    let mut nes = Nes::new(cartridge);
+    assert!(nes.cpu.bus.device_mut::<FourScore>(0).is_some());
+
+    // byte 15 means nothing to iNES 1.0
+    rom[7] = 0x00;
+    assert_eq!(Cartridge::parse(&rom).unwrap().input, InputType::STANDARD);
+}
+
+#[test]
+fn input_types_parse_by_name() {
+    assert_eq!("FourScore".parse(), Ok(InputType::FOURSCORE));
+    assert_eq!("famicom4p".parse(), Ok(InputType::FAMICOM4P));
+    assert!("keyboard".parse::<InputType>().is_err());
+}
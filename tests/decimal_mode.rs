@@ -0,0 +1,207 @@
+mod common;
+
+use common::{cpu_with_program, step};
+use rs6502::bus::CpuBus;
+use rs6502::cpu::{Status, Variant, CPU};
+use rs6502::memory::FlatMemory;
+
+fn run(variant: Variant, program: &[u8], a: u8, carry: bool) -> CPU {
+    let mut cpu = cpu_with_program(program);
+    cpu.variant = variant;
+    cpu.a = a;
+    cpu.status.set(Status::D, true);
+    cpu.status.set(Status::C, carry);
+    step(&mut cpu);
+    cpu
+}
+
+#[test]
+fn rp2a03_ignores_decimal_flag() {
+    let cpu = run(Variant::RP2A03, &[0x69, 0x01], 0x09, false);
+    assert_eq!(cpu.a, 0x0A);
+
+    let cpu = run(Variant::RP2A03, &[0xE9, 0x01], 0x10, true);
+    assert_eq!(cpu.a, 0x0F);
+}
+
+#[test]
+fn nmos_adc_adds_bcd() {
+    let cpu = run(Variant::NMOS6502, &[0x69, 0x01], 0x09, false);
+    assert_eq!(cpu.a, 0x10);
+    assert!(!cpu.status.contains(Status::C));
+
+    let cpu = run(Variant::NMOS6502, &[0x69, 0x46], 0x58, true);
+    assert_eq!(cpu.a, 0x05);
+    assert!(cpu.status.contains(Status::C));
+}
+
+#[test]
+fn nmos_adc_flags_come_from_intermediate_results() {
+    // 99 + 01 = 00 carry 1, but Z follows the binary sum ($9A) and N the
+    // half-adjusted one ($A0)
+    let cpu = run(Variant::NMOS6502, &[0x69, 0x01], 0x99, false);
+    assert_eq!(cpu.a, 0x00);
+    assert!(cpu.status.contains(Status::C));
+    assert!(!cpu.status.contains(Status::Z));
+    assert!(cpu.status.contains(Status::N));
+    assert!(!cpu.status.contains(Status::V));
+
+    // 79 + 00 + 1: the half-adjusted sum $80 overflows
+    let cpu = run(Variant::NMOS6502, &[0x69, 0x00], 0x79, true);
+    assert_eq!(cpu.a, 0x80);
+    assert!(cpu.status.contains(Status::V));
+    assert!(cpu.status.contains(Status::N));
+}
+
+#[test]
+fn nmos_sbc_subtracts_bcd() {
+    let cpu = run(Variant::NMOS6502, &[0xE9, 0x12], 0x46, true);
+    assert_eq!(cpu.a, 0x34);
+    assert!(cpu.status.contains(Status::C));
+
+    let cpu = run(Variant::NMOS6502, &[0xE9, 0x13], 0x40, true);
+    assert_eq!(cpu.a, 0x27);
+
+    let cpu = run(Variant::NMOS6502, &[0xE9, 0x01], 0x00, true);
+    assert_eq!(cpu.a, 0x99);
+    assert!(!cpu.status.contains(Status::C));
+    assert!(cpu.status.contains(Status::N));
+    assert!(!cpu.status.contains(Status::Z));
+}
+
+#[test]
+fn nmos_isb_and_rra_use_decimal_arithmetic() {
+    // ISB $10: $10 = 08 -> 09, A = 20 - 09
+    let mut cpu = cpu_with_program(&[0xE7, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x08);
+    cpu.variant = Variant::NMOS6502;
+    cpu.a = 0x20;
+    cpu.status.set(Status::D, true);
+    cpu.status.set(Status::C, true);
+    step(&mut cpu);
+    assert_eq!(cpu.a, 0x11);
+
+    // RRA $10: $10 = 10 -> 08 carry 0, A = 11 + 08
+    let mut cpu = cpu_with_program(&[0x67, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x10);
+    cpu.variant = Variant::NMOS6502;
+    cpu.a = 0x11;
+    cpu.status.set(Status::D, true);
+    cpu.status.set(Status::C, false);
+    step(&mut cpu);
+    assert_eq!(cpu.a, 0x19);
+}
+
+// Bruce Clark's model of decimal ADC and SBC ("Decimal Mode", 6502.org tutorial, appendix
+// B), the same one Klaus Dormann's 6502_decimal_test checks against: (A, N, V, Z, C).
+fn model_adc(cmos: bool, a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let (a, b, c) = (i32::from(a), i32::from(b), i32::from(carry));
+    let half = |lo: i32| if lo >= 0x0A { ((lo + 0x06) & 0x0F) + 0x10 } else { lo };
+
+    let lo = half((a & 0x0F) + (b & 0x0F) + c);
+    let mut sum = (a & 0xF0) + (b & 0xF0) + lo;
+    if sum >= 0xA0 {
+        sum += 0x60;
+    }
+    let result = sum as u8;
+
+    // N and V come from the half-adjusted sum, taken as signed
+    let signed = i32::from((a as u8) as i8 & !0x0F) + i32::from((b as u8) as i8 & !0x0F) + lo;
+    let n = signed & 0x80 != 0;
+    let v = !(-128..=127).contains(&signed);
+    let z = (a + b + c) & 0xFF == 0;
+
+    if cmos {
+        (result, result & 0x80 != 0, v, result == 0, sum >= 0x100)
+    } else {
+        (result, n, v, z, sum >= 0x100)
+    }
+}
+
+fn model_sbc(cmos: bool, a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let (a, b, c) = (i32::from(a), i32::from(b), i32::from(carry));
+
+    // the flags are the binary subtraction's, except N and Z on the 65C02
+    let binary = a - b + c - 1;
+    let n = binary & 0x80 != 0;
+    let v = ((a ^ b) & (a ^ binary) & 0x80) != 0;
+    let z = binary & 0xFF == 0;
+    let carry_out = binary >= 0;
+
+    let lo = (a & 0x0F) - (b & 0x0F) + c - 1;
+    let result = if cmos {
+        let mut diff = binary;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        if lo < 0 {
+            diff -= 0x06;
+        }
+        diff as u8
+    } else {
+        let lo = if lo < 0 { ((lo - 0x06) & 0x0F) - 0x10 } else { lo };
+        let mut diff = (a & 0xF0) - (b & 0xF0) + lo;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        diff as u8
+    };
+
+    if cmos {
+        (result, result & 0x80 != 0, v, result == 0, carry_out)
+    } else {
+        (result, n, v, z, carry_out)
+    }
+}
+
+// every accumulator, operand and carry in, valid BCD or not, as the decimal test does
+fn exhaustive(variant: Variant) {
+    let cmos = variant == Variant::WDC65C02;
+    // ADC $10 / SBC $10
+    let mut cpu = CPU::new(FlatMemory::from_rom(&[0x65, 0x10, 0xE5, 0x10]).unwrap());
+    cpu.variant = variant;
+
+    let mut failures = Vec::new();
+    for (name, pc, model) in [("ADC", 0x8000, model_adc as fn(_, _, _, _) -> _), ("SBC", 0x8002, model_sbc)] {
+        for a in 0..=0xFF {
+            for b in 0..=0xFF {
+                for carry in [false, true] {
+                    cpu.reset();
+                    cpu.pc = pc;
+                    cpu.a = a;
+                    cpu.bus.write(0x0010, b);
+                    cpu.status.set(Status::D, true);
+                    cpu.status.set(Status::C, carry);
+                    step(&mut cpu);
+
+                    let status = cpu.status;
+                    let ours = (
+                        cpu.a,
+                        status.contains(Status::N),
+                        status.contains(Status::V),
+                        status.contains(Status::Z),
+                        status.contains(Status::C),
+                    );
+                    let expected = model(cmos, a, b, carry);
+                    if ours != expected {
+                        failures.push(format!("{a:02X} {name} {b:02X} C={carry}: {ours:?}, expected {expected:?}"));
+                    }
+                }
+            }
+        }
+    }
+
+    let count = failures.len();
+    failures.truncate(10);
+    assert_eq!(count, 0, "{count} mismatches:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn nmos_decimal_mode_matches_the_reference_model() {
+    exhaustive(Variant::NMOS6502);
+}
+
+#[test]
+fn cmos_decimal_mode_matches_the_reference_model() {
+    exhaustive(Variant::WDC65C02);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{cpu_with_program, step, PROGRAM_START};
+use rs6502::bus::CpuBus;
+use rs6502::cpu::{State, CPU};
+use rs6502::diagnostics::{Diagnostic, DiagnosticHandler};
+use rs6502::memory::FlatMemory;
+
+type Seen = Arc<Mutex<Vec<Diagnostic>>>;
+
+fn recorder() -> (Seen, DiagnosticHandler) {
+    let seen = Seen::default();
+    let sink = seen.clone();
+    (seen, Arc::new(move |diagnostic| sink.lock().unwrap().push(diagnostic)))
+}
+
+fn collect(cpu: &mut CPU) -> Seen {
+    let (seen, handler) = recorder();
+    cpu.bus.diagnostics = Some(handler);
+    seen
+}
+
+// LDA #$42 / JAM, starting at `start`
+fn jams<B: CpuBus>(cpu: &mut CPU<B>, seen: &Seen, start: u16) {
+    let jam = start + 2;
+    step(cpu);
+    step(cpu);
+    assert_eq!(cpu.state, State::Halted);
+    assert_eq!(cpu.pc, jam);
+
+    // parked on the JAM, however long it's left running
+    for _ in 0..100 {
+        cpu.clock();
+    }
+    assert_eq!(cpu.pc, jam);
+    assert_eq!(*seen.lock().unwrap(), [Diagnostic::Jam { pc: jam, opcode: 0x02 }]);
+
+    // only a reset gets it going again
+    cpu.reset();
+    assert_eq!(cpu.state, State::Running);
+    step(cpu);
+    assert_eq!(cpu.a, 0x42);
+    assert_eq!(cpu.pc, jam);
+}
+
+const JAM_PROGRAM: [u8; 3] = [0xA9, 0x42, 0x02];
+
+#[test]
+fn apu_and_io_registers_are_mapped() {
+    // LDA #$0F / STA $4015 / STA $4017 / STA $4000 / LDA $4015 / LDX $401F
+    let mut cpu = cpu_with_program(&[
+        0xA9, 0x0F, 0x8D, 0x15, 0x40, 0x8D, 0x17, 0x40, 0x8D, 0x00, 0x40, 0xAD, 0x15, 0x40, 0xAE, 0x1F, 0x40,
+    ]);
+    let seen = collect(&mut cpu);
+    for _ in 0..6 {
+        step(&mut cpu);
+    }
+
+    // nothing answers, so the reads see the high byte of their own operand
+    assert_eq!(cpu.a, 0x40);
+    assert_eq!(cpu.x, 0x40);
+    assert!(seen.lock().unwrap().is_empty());
+}
+
+#[test]
+fn undecoded_addresses_are_reported() {
+    // LDA #$5A / STA $5000 / LDA $5001
+    let mut cpu = cpu_with_program(&[0xA9, 0x5A, 0x8D, 0x00, 0x50, 0xAD, 0x01, 0x50]);
+    let seen = collect(&mut cpu);
+    for _ in 0..3 {
+        step(&mut cpu);
+    }
+
+    assert_eq!(*seen.lock().unwrap(), [Diagnostic::UnmappedWrite(0x5000, 0x5A), Diagnostic::UnmappedRead(0x5001)]);
+}
+
+#[test]
+fn jam_halts_the_console_until_reset() {
+    let mut cpu = cpu_with_program(&JAM_PROGRAM);
+    let seen = collect(&mut cpu);
+    jams(&mut cpu, &seen, PROGRAM_START);
+}
+
+#[test]
+fn jam_halts_flat_memory_until_reset() {
+    let mut memory = FlatMemory::from_rom(&JAM_PROGRAM).unwrap();
+    let (seen, handler) = recorder();
+    memory.diagnostics = Some(handler);
+    let mut cpu = CPU::new(memory);
+    cpu.reset();
+    jams(&mut cpu, &seen, FlatMemory::ROM_START);
+}
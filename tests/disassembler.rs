@@ -0,0 +1,86 @@
+use rs6502::cpu::disasm::{disassemble, disassemble_range, Registers};
+use rs6502::cpu::Variant;
+use rs6502::memory::FlatMemory;
+
+fn memory(addr: u16, code: &[u8]) -> FlatMemory {
+    let mut mem = FlatMemory::new();
+    mem.load(addr, code).unwrap();
+    mem
+}
+
+fn live(mem: &mut FlatMemory, addr: u16, x: u8, y: u8) -> String {
+    disassemble(mem, addr, Variant::RP2A03, Some(Registers { x, y })).to_string()
+}
+
+#[test]
+fn lines_follow_the_nestest_layout() {
+    let mut mem = memory(0xC000, &[0x4C, 0xF5, 0xC5]);
+    assert_eq!(live(&mut mem, 0xC000, 0, 0), "C000  4C F5 C5  JMP $C5F5");
+
+    let mut mem = memory(0xC6BD, &[0x04, 0xA9]);
+    assert_eq!(live(&mut mem, 0xC6BD, 0, 0), "C6BD  04 A9    *NOP $A9 = 00");
+
+    let mut mem = memory(0xC72A, &[0xEA]);
+    assert_eq!(live(&mut mem, 0xC72A, 0, 0), "C72A  EA        NOP");
+}
+
+#[test]
+fn effective_addresses_for_every_indexed_mode() {
+    let mut mem = memory(0x8000, &[0xB1, 0x89]);
+    mem.load(0x0089, &[0x00, 0x03]).unwrap();
+    mem.load(0x0305, &[0x7F]).unwrap();
+    assert_eq!(live(&mut mem, 0x8000, 0, 5), "8000  B1 89     LDA ($89),Y = 0300 @ 0305 = 7F");
+
+    let mut mem = memory(0x8000, &[0xA1, 0x80]);
+    mem.load(0x0082, &[0x00, 0x02]).unwrap();
+    mem.load(0x0200, &[0x5A]).unwrap();
+    assert_eq!(live(&mut mem, 0x8000, 2, 0), "8000  A1 80     LDA ($80,X) @ 82 = 0200 = 5A");
+
+    let mut mem = memory(0x8000, &[0xB5, 0xFF]);
+    assert_eq!(live(&mut mem, 0x8000, 2, 0), "8000  B5 FF     LDA $FF,X @ 01 = 00");
+
+    let mut mem = memory(0x8000, &[0xBE, 0x00, 0x06]);
+    assert_eq!(live(&mut mem, 0x8000, 0, 0x10), "8000  BE 00 06  LDX $0600,Y @ 0610 = 00");
+
+    let mut mem = memory(0x8000, &[0x8D, 0x00, 0x03]);
+    mem.load(0x0300, &[0x89]).unwrap();
+    assert_eq!(live(&mut mem, 0x8000, 0, 0), "8000  8D 00 03  STA $0300 = 89");
+}
+
+#[test]
+fn indirect_jump_shows_the_page_wrap_bug() {
+    let mut mem = memory(0x8000, &[0x6C, 0xFF, 0x02]);
+    mem.load(0x0200, &[0xA9]).unwrap();
+    mem.load(0x02FF, &[0x00, 0x03]).unwrap();
+    assert_eq!(live(&mut mem, 0x8000, 0, 0), "8000  6C FF 02  JMP ($02FF) = A900");
+
+    let line = disassemble(&mut mem, 0x8000, Variant::WDC65C02, Some(Registers::default()));
+    assert_eq!(line.text, "JMP ($02FF) = 0300");
+}
+
+#[test]
+fn accumulator_implied_and_branches() {
+    let mut mem = memory(0x8000, &[0x4A, 0x60, 0xD0, 0xFC, 0xB0, 0x10]);
+    let lines = disassemble_range(&mut mem, 0x8000, 0x8005, Variant::RP2A03);
+    let text: Vec<_> = lines.iter().map(|l| l.text.as_str()).collect();
+    assert_eq!(text, ["LSR A", "RTS", "BNE $8000", "BCS $8016"]);
+    assert_eq!(lines[3].next_addr(), 0x8006);
+}
+
+#[test]
+fn cmos_syntax() {
+    let mut mem = memory(0x8000, &[0xB2, 0x20, 0x7C, 0x00, 0x90, 0x3F, 0x12, 0xFD, 0x03]);
+    let lines = disassemble_range(&mut mem, 0x8000, 0x8008, Variant::WDC65C02);
+    let text: Vec<_> = lines.iter().map(|l| l.text.as_str()).collect();
+    assert_eq!(text, ["LDA ($20)", "JMP ($9000,X)", "BBR3 $12,$8005", "NOP"]);
+    assert!(lines[3].unofficial);
+    assert!(!lines[2].unofficial);
+}
+
+#[test]
+fn static_dumps_leave_out_effective_addresses() {
+    let mut mem = memory(0x8000, &[0xB1, 0x89, 0xC7, 0x10]);
+    let lines = disassemble_range(&mut mem, 0x8000, 0x8003, Variant::RP2A03);
+    assert_eq!(lines[0].to_string(), "8000  B1 89     LDA ($89),Y");
+    assert_eq!(lines[1].to_string(), "8002  C7 10    *DCP $10");
+}
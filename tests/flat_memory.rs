@@ -0,0 +1,79 @@
+mod common;
+
+use common::{run_to_trap, step};
+use rs6502::cpu::CPU;
+use rs6502::memory::FlatMemory;
+
+fn cpu_with_rom(rom: &[u8]) -> CPU<FlatMemory> {
+    let mut cpu = CPU::new(FlatMemory::from_rom(rom).unwrap());
+    cpu.reset();
+    cpu
+}
+
+#[test]
+fn short_rom_images_start_at_the_top_of_rom() {
+    // LDA #$42, STA $0200, JMP *
+    let mut cpu = cpu_with_rom(&[0xA9, 0x42, 0x8D, 0x00, 0x02, 0x4C, 0x05, 0x80]);
+    assert_eq!(cpu.pc, 0x8000);
+    assert_eq!(run_to_trap(&mut cpu, 100), Some(0x8005));
+    assert_eq!(cpu.peek(0x0200), 0x42);
+}
+
+#[test]
+fn oversized_rom_images_are_rejected() {
+    assert!(FlatMemory::from_rom(&vec![0; 0x8001]).is_err());
+}
+
+#[test]
+fn brk_pushes_the_address_after_its_padding_byte() {
+    // BRK, pad, JMP *; the handler at $8010 is a bare RTI
+    let mut mem = FlatMemory::from_rom(&[0x00, 0xEA, 0x4C, 0x02, 0x80]).unwrap();
+    mem.load(0x8010, &[0x40]).unwrap();
+    mem.load(0xFFFE, &[0x10, 0x80]).unwrap();
+    let mut cpu = CPU::new(mem);
+    cpu.reset();
+
+    assert_eq!(step(&mut cpu), 7);
+    assert_eq!(cpu.pc, 0x8010);
+    assert_eq!(cpu.peek(0x01FD), 0x80);
+    assert_eq!(cpu.peek(0x01FC), 0x02);
+    // B is set in the pushed copy only
+    assert_eq!(cpu.peek(0x01FB) & 0x10, 0x10);
+
+    step(&mut cpu);
+    assert_eq!(cpu.pc, 0x8002);
+}
+
+#[test]
+fn irq_is_taken_between_instructions_unless_masked() {
+    // CLI, NOP, JMP *; the handler at $8010 spins
+    let mut mem = FlatMemory::from_rom(&[0x58, 0xEA, 0x4C, 0x02, 0x80]).unwrap();
+    mem.load(0x8010, &[0x4C, 0x10, 0x80]).unwrap();
+    mem.load(0xFFFE, &[0x10, 0x80]).unwrap();
+    let mut cpu = CPU::new(mem);
+    cpu.reset();
+
+    // still masked while CLI itself runs
+    cpu.bus.irq = true;
+    step(&mut cpu);
+    assert_eq!(cpu.pc, 0x8001);
+
+    assert_eq!(step(&mut cpu), 7);
+    assert_eq!(cpu.pc, 0x8010);
+    assert_eq!(cpu.peek(0x01FC), 0x01);
+}
+
+#[test]
+fn nmi_is_edge_triggered() {
+    let mut mem = FlatMemory::from_rom(&[0xEA, 0xEA, 0x4C, 0x02, 0x80]).unwrap();
+    mem.load(0x8010, &[0xEA, 0xEA]).unwrap();
+    mem.load(0xFFFA, &[0x10, 0x80]).unwrap();
+    let mut cpu = CPU::new(mem);
+    cpu.reset();
+
+    cpu.bus.nmi = true;
+    assert_eq!(step(&mut cpu), 7);
+    assert_eq!(cpu.pc, 0x8010);
+    step(&mut cpu);
+    assert_eq!(cpu.pc, 0x8011);
+}
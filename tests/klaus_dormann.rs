@@ -0,0 +1,60 @@
+// Klaus Dormann's 6502 functional and decimal tests. The binaries aren't redistributed
+// here, so the tests are ignored by default: assemble them from
+// https://github.com/Klaus2m5/6502_65C02_functional_tests (the prebuilt
+// bin_files/6502_functional_test.bin works as is), drop them in tests/data/ and run
+// `cargo test --test klaus_dormann -- --ignored`. The decimal test's reference model also
+// runs on every build, over every case, in decimal_mode.rs.
+mod common;
+
+use std::fs;
+
+use common::run_to_trap;
+use rs6502::cpu::{Variant, CPU};
+use rs6502::memory::FlatMemory;
+
+const FUNCTIONAL_TEST: &str = "tests/data/6502_functional_test.bin";
+const DECIMAL_TEST: &str = "tests/data/6502_decimal_test.bin";
+
+// addresses from the stock builds of the tests
+const FUNCTIONAL_START: u16 = 0x0400;
+const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+const DECIMAL_START: u16 = 0x0200;
+const DECIMAL_ERROR: u16 = 0x000B;
+
+fn load(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+#[test]
+#[ignore = "needs tests/data/6502_functional_test.bin"]
+fn functional_test() {
+    let image = load(FUNCTIONAL_TEST);
+
+    let mut mem = FlatMemory::new();
+    mem.load(0x0000, &image).unwrap();
+    let mut cpu = CPU::new(mem);
+    cpu.variant = Variant::NMOS6502;
+    cpu.reset();
+    cpu.pc = FUNCTIONAL_START;
+
+    let trap = run_to_trap(&mut cpu, 100_000_000).expect("test never finished");
+    assert_eq!(trap, FUNCTIONAL_SUCCESS, "trapped at {trap:04X}");
+}
+
+#[test]
+#[ignore = "needs tests/data/6502_decimal_test.bin"]
+fn decimal_test() {
+    let image = load(DECIMAL_TEST);
+
+    // full 64 KB images start at $0000, anything smaller was assembled to load at the start
+    let mut mem = FlatMemory::new();
+    let base = if image.len() == 0x10000 { 0x0000 } else { DECIMAL_START };
+    mem.load(base, &image).unwrap();
+    let mut cpu = CPU::new(mem);
+    cpu.variant = Variant::NMOS6502;
+    cpu.reset();
+    cpu.pc = DECIMAL_START;
+
+    run_to_trap(&mut cpu, 100_000_000).expect("test never finished");
+    assert_eq!(cpu.peek(DECIMAL_ERROR), 0, "decimal test reported an error");
+}
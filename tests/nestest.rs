@@ -0,0 +1,124 @@
+mod common;
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use common::{nestest_cpu, step};
+
+// Golden trace of the whole automation run, in nestest.log's format. It was captured from
+// this emulator once its trace agreed with the published log
+// (https://www.qmtpro.com/~nes/misc/nestest.log) on length, the final RTS and the result
+// codes. The APU register writes at the end show "= FF" as the published log does; what we
+// peek there is open bus, so the comparison overrides it (see `apu_as_published`).
+const GOLDEN_LOG: &str = include_str!("data/nestest.log");
+
+// length of the reference log, and where its last instruction (the final RTS) starts
+const INSTRUCTIONS: usize = 8991;
+const LAST_PC: &str = "C66E";
+const LAST_CYCLE: &str = "CYC:26554";
+
+// lines of context around a mismatch
+const CONTEXT: usize = 5;
+
+// io::Write into a buffer the test can still read
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_matches_the_nestest_log_format() {
+    let mut cpu = nestest_cpu();
+    let log = SharedBuf::default();
+    cpu.trace_to(log.clone());
+
+    for _ in 0..3 {
+        step(&mut cpu);
+    }
+
+    let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines, [
+        "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+        "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10",
+        "C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12",
+    ]);
+}
+
+#[test]
+fn trace_can_be_switched_off() {
+    let mut cpu = nestest_cpu();
+    let log = SharedBuf::default();
+    cpu.trace_to(log.clone());
+
+    step(&mut cpu);
+    cpu.trace_enabled = false;
+    step(&mut cpu);
+    cpu.trace_enabled = true;
+    step(&mut cpu);
+
+    let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    let pcs: Vec<_> = log.lines().map(|l| &l[..4]).collect();
+    assert_eq!(pcs, ["C000", "C5F7"]);
+}
+
+#[test]
+fn nestest_automation_mode_passes() {
+    let mut cpu = nestest_cpu();
+    let log = SharedBuf::default();
+    cpu.trace_to(log.clone());
+
+    for _ in 0..INSTRUCTIONS {
+        step(&mut cpu);
+    }
+
+    let trace = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    let trace: Vec<_> = trace.lines().collect();
+
+    compare(&trace, &GOLDEN_LOG.lines().collect::<Vec<_>>());
+
+    let last = trace.last().unwrap();
+    assert!(last.starts_with(LAST_PC), "finished at {last}");
+    assert!(last.ends_with(LAST_CYCLE), "finished at {last}");
+
+    // nestest leaves an error code for the official opcodes in $02 and the unofficial ones in $03
+    assert_eq!(cpu.peek(0x0002), 0x00, "official opcode tests failed");
+    assert_eq!(cpu.peek(0x0003), 0x00, "unofficial opcode tests failed");
+}
+
+// The "= xx" on $4000-$4017 lines is whatever was last on the bus, which depends on more
+// than nestest does. The published log has FF there.
+fn apu_as_published(line: &str) -> String {
+    let mut line = line.to_string();
+    if let Some(at) = line.find(" $40") {
+        let register = line.get(at + 2..at + 6).and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        let value = at + 6 + " = ".len();
+        if register.is_some_and(|r| r <= 0x4017) && line[at + 6..].starts_with(" = ") {
+            line.replace_range(value..value + 2, "FF");
+        }
+    }
+    line
+}
+
+fn compare(trace: &[&str], golden: &[&str]) {
+    for (i, (ours, theirs)) in trace.iter().zip(golden).enumerate() {
+        let ours = apu_as_published(ours);
+        if ours.trim_end() != theirs.trim_end() {
+            let from = i.saturating_sub(CONTEXT);
+            let context = golden[from..i].join("\n");
+            panic!(
+                "trace diverged at instruction {}:\n{context}\nexpected: {theirs}\n     got: {ours}",
+                i + 1
+            );
+        }
+    }
+    assert_eq!(trace.len(), golden.len(), "trace and golden log differ in length");
+}
@@ -0,0 +1,238 @@
+use rs6502::cartridge::Cartridge;
+use rs6502::ppu::{OpenBus, PPU};
+use rs6502::region::Region;
+
+const RENDERING_ON: u8 = 0x1E;
+
+fn nestest() -> (PPU, Cartridge) {
+    let cart = Cartridge::new("nestest.nes".to_string()).unwrap();
+    (PPU::new(cart.region), cart)
+}
+
+// (frame number, dots it took) for a run of whole frames
+fn frame_lengths(region: Region, mask: u8, frames: usize) -> Vec<(u32, usize)> {
+    let (_, mut cart) = nestest();
+    let mut ppu = PPU::new(region);
+    ppu.cpu_write(0x0001, mask, &mut cart);
+
+    let start = ppu.frame_number();
+    while ppu.frame_number() == start {
+        ppu.clock(&cart);
+    }
+
+    (0..frames)
+        .map(|_| {
+            let frame = ppu.frame_number();
+            let mut dots = 0;
+            while ppu.frame_number() == frame {
+                ppu.clock(&cart);
+                dots += 1;
+            }
+            (frame, dots)
+        })
+        .collect()
+}
+
+#[test]
+fn ntsc_skips_a_dot_on_odd_frames_while_rendering() {
+    let lengths = frame_lengths(Region::NTSC, RENDERING_ON, 6);
+    for (frame, dots) in lengths {
+        let expected = if frame % 2 == 1 { 341 * 262 - 1 } else { 341 * 262 };
+        assert_eq!(dots, expected, "frame {frame}");
+    }
+}
+
+#[test]
+fn ntsc_frames_are_all_full_length_with_rendering_off() {
+    for (frame, dots) in frame_lengths(Region::NTSC, 0x00, 4) {
+        assert_eq!(dots, 341 * 262, "frame {frame}");
+    }
+}
+
+#[test]
+fn pal_and_dendy_frames_are_312_lines_without_a_skip() {
+    for region in [Region::PAL, Region::DENDY] {
+        for (frame, dots) in frame_lengths(region, RENDERING_ON, 4) {
+            assert_eq!(dots, 341 * 312, "{region:?} frame {frame}");
+        }
+    }
+}
+
+#[test]
+fn scroll_writes_fill_in_t_x_and_w() {
+    let (mut ppu, mut cart) = nestest();
+
+    // $2000: nametable select goes to t bits 10-11
+    ppu.cpu_write(0x0000, 0x02, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x0800);
+
+    // $2002 read clears w
+    ppu.cpu_read(0x0002, false, &cart);
+    assert!(!ppu.scroll().write_latch);
+
+    // first $2005: coarse x into t, fine x into x
+    ppu.cpu_write(0x0005, 0x7D, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x080F);
+    assert_eq!(ppu.scroll().fine_x, 0x05);
+    assert!(ppu.scroll().write_latch);
+
+    // second $2005: coarse and fine y into t
+    ppu.cpu_write(0x0005, 0x5E, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x696F);
+    assert!(!ppu.scroll().write_latch);
+
+    // first $2006: the high 6 bits of t, and bit 14 cleared
+    ppu.cpu_write(0x0006, 0x3D, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x3D6F);
+    assert!(ppu.scroll().write_latch);
+
+    // second $2006: the low byte, and then t goes to v
+    ppu.cpu_write(0x0006, 0xF0, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x3DF0);
+    assert!(!ppu.scroll().write_latch);
+    for _ in 0..3 {
+        ppu.clock(&cart);
+    }
+    assert_eq!(ppu.scroll().v, 0x3DF0);
+    assert_eq!(ppu.scroll().fine_x, 0x05);
+}
+
+#[test]
+fn the_second_2006_write_reaches_v_a_little_later() {
+    let (mut ppu, mut cart) = nestest();
+    ppu.cpu_write(0x0006, 0x21, &mut cart);
+    ppu.cpu_write(0x0006, 0x08, &mut cart);
+    assert_eq!(ppu.scroll().v, 0x0000);
+
+    ppu.clock(&cart);
+    ppu.clock(&cart);
+    assert_eq!(ppu.scroll().v, 0x2108);
+}
+
+#[test]
+fn status_reads_restart_the_write_pair() {
+    let (mut ppu, mut cart) = nestest();
+    ppu.cpu_write(0x0005, 0x08, &mut cart);
+    ppu.cpu_read(0x0002, false, &cart);
+
+    // taken as a first write again: coarse x, not y
+    ppu.cpu_write(0x0005, 0x10, &mut cart);
+    assert_eq!(ppu.scroll().t, 0x0002);
+    assert!(ppu.scroll().write_latch);
+
+    // peeks leave w alone
+    ppu.cpu_read(0x0002, true, &cart);
+    assert!(ppu.scroll().write_latch);
+}
+
+#[test]
+fn scroll_and_address_writes_share_the_latch() {
+    let (mut ppu, mut cart) = nestest();
+
+    // the mid-frame scroll change: nametable, Y, X, then the low byte of v
+    ppu.cpu_write(0x0006, 0x04, &mut cart);
+    ppu.cpu_write(0x0005, 0x40, &mut cart);
+    ppu.cpu_write(0x0005, 0x33, &mut cart);
+    ppu.cpu_write(0x0006, 0x06, &mut cart);
+    for _ in 0..3 {
+        ppu.clock(&cart);
+    }
+
+    assert_eq!(ppu.scroll().v, 0x0506);
+    assert_eq!(ppu.scroll().fine_x, 0x03);
+    assert!(!ppu.scroll().write_latch);
+}
+
+// 600 ms of NTSC PPU dots
+const DECAY_DOTS: u64 = 5_369_318 * 600 / 1000;
+
+#[test]
+fn open_bus_bits_leak_away_unless_refreshed() {
+    let mut open_bus = OpenBus::new(Region::NTSC);
+    open_bus.write(0xFF, 0);
+    assert_eq!(open_bus.read(DECAY_DOTS), 0xFF);
+    assert_eq!(open_bus.read(DECAY_DOTS + 1), 0x00);
+
+    // refreshing some bits keeps only those alive
+    open_bus.write(0xFF, 0);
+    open_bus.refresh(0xA0, 0xE0, DECAY_DOTS / 2);
+    assert_eq!(open_bus.read(DECAY_DOTS + 1), 0xA0);
+    assert_eq!(open_bus.read(DECAY_DOTS / 2 + DECAY_DOTS + 1), 0x00);
+}
+
+#[test]
+fn status_reads_return_the_latched_low_bits_until_they_decay() {
+    let (mut ppu, mut cart) = nestest();
+
+    // any register write charges the whole latch
+    ppu.cpu_write(0x0000, 0x1F, &mut cart);
+    assert_eq!(ppu.cpu_read(0x0002, false, &cart) & 0x1F, 0x1F);
+    // and write-only registers read it back
+    assert_eq!(ppu.cpu_read(0x0005, false, &cart), 0x1F);
+
+    // a $2002 read only drives the top three bits, so the rest keep leaking
+    for _ in 0..DECAY_DOTS {
+        ppu.clock(&cart);
+    }
+    assert_eq!(ppu.cpu_read(0x0002, false, &cart) & 0x1F, 0x1F);
+    for _ in 0..2 {
+        ppu.clock(&cart);
+    }
+    assert_eq!(ppu.cpu_read(0x0002, false, &cart) & 0x1F, 0x00);
+    assert_eq!(ppu.cpu_read(0x0005, false, &cart), 0x00);
+}
+
+// run() has to end up exactly where clock() called as many times does, however the CPU
+// breaks the dots up with register writes
+#[test]
+fn running_in_batches_matches_clocking_dot_by_dot() {
+    let (mut stepped, mut cart) = nestest();
+
+    // something to draw: a nametable of varied tiles and attributes, and a palette
+    stepped.cpu_write(0x0006, 0x20, &mut cart);
+    stepped.cpu_write(0x0006, 0x00, &mut cart);
+    for i in 0..0x800u32 {
+        stepped.cpu_write(0x0007, (i * 7 + i / 32) as u8, &mut cart);
+    }
+    stepped.cpu_write(0x0006, 0x3F, &mut cart);
+    stepped.cpu_write(0x0006, 0x00, &mut cart);
+    for i in 0..0x20u8 {
+        stepped.cpu_write(0x0007, i * 3 + 1, &mut cart);
+    }
+    let mut batched = stepped.clone();
+
+    // a fixed pseudo-random run of scroll, mask, control and address writes
+    let mut seed = 0x2C0F_u32;
+    let mut next = |max: u32| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (seed >> 8) % max
+    };
+    for _ in 0..3000 {
+        let dots = next(2000) + 1;
+        for _ in 0..dots {
+            stepped.clock(&cart);
+        }
+        batched.run(dots.into(), &cart);
+
+        let (reg, data) = match next(6) {
+            0 => (0x0000, next(256) as u8 & 0x1B),
+            1 => (0x0001, [0x00, 0x08, 0x0A, 0x1E, 0x18, 0x1F][next(6) as usize]),
+            2 | 3 => (0x0005, next(256) as u8),
+            4 => (0x0006, next(64) as u8),
+            _ => (0x0002, 0),
+        };
+        stepped.cpu_write(reg, data, &mut cart);
+        batched.cpu_write(reg, data, &mut cart);
+        if reg == 0x0002 {
+            stepped.cpu_read(reg, false, &cart);
+            batched.cpu_read(reg, false, &cart);
+        }
+
+        assert_eq!(format!("{:?}", batched.scroll()), format!("{:?}", stepped.scroll()));
+        assert!(batched.screen == stepped.screen, "frame {} differs", stepped.frame_number());
+    }
+
+    assert_eq!(batched.frame_number(), stepped.frame_number());
+    assert!(batched.frame_number() > 20);
+    assert_eq!(format!("{batched:?}"), format!("{stepped:?}"));
+}
@@ -0,0 +1,67 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn run(name: &str, rom: &[u8], args: &[&str], input: &[u8]) -> Output {
+    let path: PathBuf = std::env::temp_dir().join(format!("run6502-{}-{name}.bin", std::process::id()));
+    std::fs::write(&path, rom).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_run6502"))
+        .arg(&path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let _ = std::fs::remove_file(path);
+    output
+}
+
+#[test]
+fn echoes_through_the_character_ports_and_exits_with_the_program_status() {
+    let rom = [
+        0xA9, b'H', 0x8D, 0x00, 0x7F, // LDA #'H', STA $7F00
+        0xA9, b'I', 0x8D, 0x00, 0x7F, // LDA #'I', STA $7F00
+        0xAD, 0x01, 0x7F, 0x8D, 0x00, 0x7F, // LDA $7F01, STA $7F00
+        0xAD, 0x01, 0x7F, 0x8D, 0x00, 0x7F, // LDA $7F01, STA $7F00
+        0xA9, 0x03, 0x8D, 0x02, 0x7F, // LDA #3, STA $7F02
+    ];
+
+    let output = run("echo", &rom, &[], b"!");
+    assert_eq!(output.stdout, b"HI!\xFF");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn stopped_cpu_fails() {
+    let output = run("jam", &[0x02], &[], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn gives_up_after_max_cycles() {
+    // JMP *
+    let output = run("spin", &[0x4C, 0x00, 0x80], &["--max-cycles", "1000"], b"");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn max_cycles_counts_cpu_cycles() {
+    // LDX #0, DEX / BNE back 256 times, LDA #3, STA $7F02: 1294 cycles with the reset
+    let rom = [0xA2, 0x00, 0xCA, 0xD0, 0xFD, 0xA9, 0x03, 0x8D, 0x02, 0x7F];
+    assert_eq!(run("count", &rom, &["--max-cycles", "1300"], b"").status.code(), Some(3));
+    assert_eq!(run("short", &rom, &["--max-cycles", "1290"], b"").status.code(), Some(1));
+}
+
+#[test]
+fn cpu_can_be_selected() {
+    // INC A x3, STA $7F02: INC A is a 65C02 addition, NMOS parts treat $1A as a NOP
+    let rom = [0x1A, 0x1A, 0x1A, 0x8D, 0x02, 0x7F];
+    assert_eq!(run("cmos", &rom, &["--cpu", "65c02"], b"").status.code(), Some(3));
+    assert_eq!(run("nmos", &rom, &["--cpu", "6502"], b"").status.code(), Some(0));
+}
@@ -0,0 +1,225 @@
+// PPU output against the images in tests/golden/. When a change to the PPU is meant to alter
+// what's drawn, look over the .diff.png files and rerun with BLESS=1 to accept.
+use std::path::Path;
+
+use rs6502::cartridge::Cartridge;
+use rs6502::nes::Nes;
+use rs6502::screenshot::{capture, check_golden, Frame, Input};
+
+const GOLDEN_DIR: &str = "tests/golden";
+
+const START: u8 = 0x10;
+const DOWN: u8 = 0x04;
+
+fn golden_test(rom: &str, name: &str, frames: u32, script: &[Input]) {
+    golden_test_cart(Cartridge::new(rom.to_string()).unwrap(), name, frames, script);
+}
+
+fn golden_test_cart(cartridge: Cartridge, name: &str, frames: u32, script: &[Input]) {
+    let mut nes = Nes::new(cartridge);
+    nes.reset();
+    let frame = capture(&mut nes, frames, script);
+
+    let golden = Path::new(GOLDEN_DIR).join(format!("{name}.png"));
+    if let Err(e) = check_golden(&frame, &golden) {
+        panic!("{e}");
+    }
+}
+
+#[test]
+fn nestest_menu() {
+    golden_test("nestest.nes", "nestest_menu", 30, &[]);
+}
+
+#[test]
+fn nestest_runs_the_official_opcode_tests() {
+    let script = [
+        Input { frame: 10, buttons: START },
+        Input { frame: 12, buttons: 0x00 },
+    ];
+    golden_test("nestest.nes", "nestest_official", 120, &script);
+}
+
+#[test]
+fn nestest_moves_the_cursor() {
+    let script = [
+        Input { frame: 10, buttons: DOWN },
+        Input { frame: 12, buttons: 0x00 },
+    ];
+    golden_test("nestest.nes", "nestest_cursor", 30, &script);
+}
+
+// NROM image with a status bar at the top and two timed scroll splits below it: the first
+// sets the horizontal scroll, which moves a pixel every frame, and a $2006/$2005 sequence
+// further down switches to the second nametable scrolled in both directions
+fn split_screen_rom() -> Vec<u8> {
+    #[rustfmt::skip]
+    let program = [
+        0x78,                   // 8000  SEI
+        0xD8,                   // 8001  CLD
+        0xA2, 0xFF,             // 8002  LDX #$FF
+        0x9A,                   // 8004  TXS
+        0x2C, 0x02, 0x20,       // 8005  BIT $2002    wait for the PPU to warm up
+        0x10, 0xFB,             // 8008  BPL $8005
+        0x2C, 0x02, 0x20,       // 800A  BIT $2002
+        0x10, 0xFB,             // 800D  BPL $800A
+        0xA9, 0x3F,             // 800F  LDA #$3F     palettes from $9800
+        0x8D, 0x06, 0x20,       // 8011  STA $2006
+        0xA9, 0x00,             // 8014  LDA #$00
+        0x8D, 0x06, 0x20,       // 8016  STA $2006
+        0xA2, 0x00,             // 8019  LDX #$00
+        0xBD, 0x00, 0x98,       // 801B  LDA $9800,X
+        0x8D, 0x07, 0x20,       // 801E  STA $2007
+        0xE8,                   // 8021  INX
+        0xE0, 0x20,             // 8022  CPX #$20
+        0xD0, 0xF5,             // 8024  BNE $801B
+        0xA9, 0x00,             // 8026  LDA #$00     both nametables from $9000
+        0x85, 0x00,             // 8028  STA $00
+        0xA9, 0x90,             // 802A  LDA #$90
+        0x85, 0x01,             // 802C  STA $01
+        0xA9, 0x20,             // 802E  LDA #$20
+        0x8D, 0x06, 0x20,       // 8030  STA $2006
+        0xA9, 0x00,             // 8033  LDA #$00
+        0x8D, 0x06, 0x20,       // 8035  STA $2006
+        0xA2, 0x08,             // 8038  LDX #$08
+        0xA0, 0x00,             // 803A  LDY #$00
+        0xB1, 0x00,             // 803C  LDA ($00),Y
+        0x8D, 0x07, 0x20,       // 803E  STA $2007
+        0xC8,                   // 8041  INY
+        0xD0, 0xF8,             // 8042  BNE $803C
+        0xE6, 0x01,             // 8044  INC $01
+        0xCA,                   // 8046  DEX
+        0xD0, 0xF3,             // 8047  BNE $803C
+        0xA9, 0x00,             // 8049  LDA #$00
+        0x8D, 0x05, 0x20,       // 804B  STA $2005
+        0x8D, 0x05, 0x20,       // 804E  STA $2005
+        0x8D, 0x00, 0x20,       // 8051  STA $2000
+        0xA9, 0x0A,             // 8054  LDA #$0A     background on
+        0x8D, 0x01, 0x20,       // 8056  STA $2001
+        0x2C, 0x02, 0x20,       // 8059  BIT $2002    each frame: wait for vblank
+        0x10, 0xFB,             // 805C  BPL $8059
+        0xA9, 0x00,             // 805E  LDA #$00     status bar unscrolled
+        0x8D, 0x05, 0x20,       // 8060  STA $2005
+        0x8D, 0x05, 0x20,       // 8063  STA $2005
+        0x8D, 0x00, 0x20,       // 8066  STA $2000
+        0xE6, 0x10,             // 8069  INC $10
+        0xA2, 0x06,             // 806B  LDX #$06     about 68 lines, to line 47
+        0xA0, 0x00,             // 806D  LDY #$00
+        0x88,                   // 806F  DEY
+        0xD0, 0xFD,             // 8070  BNE $806F
+        0xCA,                   // 8072  DEX
+        0xD0, 0xF8,             // 8073  BNE $806D
+        0xA5, 0x10,             // 8075  LDA $10      first split: X only
+        0x8D, 0x05, 0x20,       // 8077  STA $2005
+        0xA9, 0x00,             // 807A  LDA #$00
+        0x8D, 0x05, 0x20,       // 807C  STA $2005
+        0xA2, 0x05,             // 807F  LDX #$05     about 57 more
+        0xA0, 0x00,             // 8081  LDY #$00
+        0x88,                   // 8083  DEY
+        0xD0, 0xFD,             // 8084  BNE $8083
+        0xCA,                   // 8086  DEX
+        0xD0, 0xF8,             // 8087  BNE $8081
+        0xA9, 0x04,             // 8089  LDA #$04     second split: nametable 1
+        0x8D, 0x06, 0x20,       // 808B  STA $2006
+        0xA9, 0x40,             // 808E  LDA #$40     Y = 64
+        0x8D, 0x05, 0x20,       // 8090  STA $2005
+        0xA9, 0x33,             // 8093  LDA #$33     X = 51
+        0x8D, 0x05, 0x20,       // 8095  STA $2005
+        0xA9, 0x06,             // 8098  LDA #$06     (Y & $F8) << 2 | X >> 3
+        0x8D, 0x06, 0x20,       // 809A  STA $2006
+        0x4C, 0x59, 0x80,       // 809D  JMP $8059
+        0x40,                   // 80A0  RTI
+    ];
+
+    #[rustfmt::skip]
+    let palettes = [
+        0x0F, 0x30, 0x16, 0x27, 0x0F, 0x2A, 0x12, 0x30, 0x0F, 0x21, 0x28, 0x16, 0x0F, 0x14, 0x38, 0x1A,
+        0x0F, 0x30, 0x30, 0x30, 0x0F, 0x30, 0x30, 0x30, 0x0F, 0x30, 0x30, 0x30, 0x0F, 0x30, 0x30, 0x30,
+    ];
+
+    // 1-3 solid in colors 1-3, then a diagonal, a checkerboard, and stripes each way
+    let mut chr = vec![0; 0x2000];
+    for row in 0..8 {
+        let tiles: [(u8, u8); 7] = [
+            (0xFF, 0x00),
+            (0x00, 0xFF),
+            (0xFF, 0xFF),
+            (0x80 >> row, 0x00),
+            if row < 4 { (0xF0, 0x0F) } else { (0x0F, 0xF0) },
+            if row % 2 == 0 { (0xFF, 0x00) } else { (0x00, 0xFF) },
+            (0xCC, 0x33),
+        ];
+        for (i, (lo, hi)) in tiles.into_iter().enumerate() {
+            chr[(i + 1) * 16 + row] = lo;
+            chr[(i + 1) * 16 + 8 + row] = hi;
+        }
+    }
+
+    // nametable 0: a five-row status bar over diagonal bands, nametable 1: blocks
+    let mut nametables = vec![0; 0x800];
+    for row in 0..30 {
+        for col in 0..32 {
+            nametables[row * 32 + col] = match row {
+                2 => 5,
+                0..=4 => 1,
+                _ => [2, 4, 5, 6, 7][(col + 2 * row) % 5],
+            };
+            nametables[0x400 + row * 32 + col] = [4, 6, 7, 3][(col / 2 + row / 2) % 4];
+        }
+    }
+    for i in 0..64 {
+        nametables[0x3C0 + i] = if i < 8 { 0x00 } else { (i % 4) as u8 * 0x55 };
+        nametables[0x7C0 + i] = 0xE4;
+    }
+
+    let mut prg = vec![0; 0x4000];
+    prg[..program.len()].copy_from_slice(&program);
+    prg[0x1000..0x1800].copy_from_slice(&nametables);
+    prg[0x1800..0x1820].copy_from_slice(&palettes);
+    prg[0x3FFA..].copy_from_slice(&[0xA0, 0x80, 0x00, 0x80, 0xA0, 0x80]);
+
+    // vertical mirroring, so the two nametables are side by side
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    rom.extend(prg);
+    rom.extend(chr);
+    rom
+}
+
+#[test]
+fn split_screen_scrolling() {
+    let cartridge = Cartridge::parse(&split_screen_rom()).unwrap();
+    golden_test_cart(cartridge, "split_screen", 40, &[]);
+}
+
+#[test]
+fn frames_survive_a_png_round_trip() {
+    let frame = Frame {
+        width: 2,
+        height: 2,
+        pixels: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    };
+    let path = std::env::temp_dir().join(format!("rs6502-roundtrip-{}.png", std::process::id()));
+    frame.save_png(&path).unwrap();
+    let loaded = Frame::load_png(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, frame);
+    assert_eq!(loaded.hash(), frame.hash());
+}
+
+#[test]
+fn diff_marks_changed_pixels() {
+    let a = Frame {
+        width: 2,
+        height: 1,
+        pixels: vec![30, 30, 30, 0, 0, 0],
+    };
+    let mut b = a.clone();
+    assert_eq!(a.diff(&b), None);
+
+    b.pixels[3] = 0xFF;
+    let (count, diff) = a.diff(&b).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(diff.pixels, vec![15, 15, 15, 0xFF, 0x00, 0x00]);
+    assert_ne!(a.hash(), b.hash());
+}
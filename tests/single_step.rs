@@ -0,0 +1,228 @@
+// Tom Harte's SingleStepTests: for every opcode, 10,000 cases of registers and memory before
+// and after one instruction, with every bus cycle in between. The suite is too big to
+// redistribute here, so the full runs are ignored by default: clone
+// https://github.com/SingleStepTests/65x02 into tests/data/65x02/ and run
+// `cargo test --release --test single_step -- --ignored`.
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::step;
+use rs6502::bus::CpuBus;
+use rs6502::cpu::{State, Status, Variant, CPU};
+use rs6502::memory::FlatMemory;
+use serde::Deserialize;
+
+const SUITE_DIR: &str = "tests/data/65x02";
+
+// failures to show per opcode
+const SHOWN: usize = 3;
+
+#[derive(Deserialize)]
+struct Case {
+    name: String,
+    initial: Snapshot,
+    #[serde(rename = "final")]
+    expected: Snapshot,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+// flat memory that keeps a log of the bus cycles, in the suite's format
+struct Recorder {
+    mem: FlatMemory,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+impl CpuBus for Recorder {
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = self.mem.read(addr);
+        self.cycles.push((addr, data, "read".to_string()));
+        data
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.mem.write(addr, data);
+        self.cycles.push((addr, data, "write".to_string()));
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.mem.peek(addr)
+    }
+}
+
+// B and U only exist in pushed copies of P, which the RAM comparison already covers
+fn flags(p: u8) -> u8 {
+    p | (Status::B | Status::U).bits()
+}
+
+// Runs one case and describes what differs. None if the CPU stopped on a JAM: the suite
+// has it spinning through bus cycles, ours halts for good, so there's nothing to compare.
+fn run(case: &Case, variant: Variant) -> Option<Result<(), String>> {
+    let mut mem = FlatMemory::new();
+    for &(addr, data) in &case.initial.ram {
+        mem.write(addr, data);
+    }
+    let mut cpu = CPU::new(Recorder { mem, cycles: Vec::new() });
+    cpu.variant = variant;
+    cpu.pc = case.initial.pc;
+    cpu.sp = case.initial.s;
+    cpu.a = case.initial.a;
+    cpu.x = case.initial.x;
+    cpu.y = case.initial.y;
+    cpu.status = Status::from_bits_truncate(case.initial.p);
+
+    step(&mut cpu);
+    if cpu.state != State::Running {
+        return None;
+    }
+
+    let want = &case.expected;
+    let mut diffs = Vec::new();
+    let registers = [
+        ("PC", u32::from(cpu.pc), u32::from(want.pc)),
+        ("S", cpu.sp.into(), want.s.into()),
+        ("A", cpu.a.into(), want.a.into()),
+        ("X", cpu.x.into(), want.x.into()),
+        ("Y", cpu.y.into(), want.y.into()),
+        ("P", flags(cpu.status.bits()).into(), flags(want.p).into()),
+    ];
+    for (name, ours, theirs) in registers {
+        if ours != theirs {
+            diffs.push(format!("{name} {ours:02X}, expected {theirs:02X}"));
+        }
+    }
+    for &(addr, data) in &want.ram {
+        let ours = cpu.peek(addr);
+        if ours != data {
+            diffs.push(format!("${addr:04X} = {ours:02X}, expected {data:02X}"));
+        }
+    }
+    if cpu.bus.cycles != case.cycles {
+        diffs.push(format!("bus cycles {:?}, expected {:?}", cpu.bus.cycles, case.cycles));
+    }
+
+    if diffs.is_empty() {
+        Some(Ok(()))
+    } else {
+        Some(Err(format!("{}: {}", case.name, diffs.join("; "))))
+    }
+}
+
+// every case that doesn't match, skipping only the ones that JAM
+fn mismatches(cases: &[Case], variant: Variant) -> Vec<String> {
+    cases.iter().filter_map(|case| run(case, variant)?.err()).collect()
+}
+
+fn find_cases(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            find_cases(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+}
+
+fn suite(cpu: &str, variant: Variant) {
+    let dir = Path::new(SUITE_DIR).join(cpu);
+    let mut files = Vec::new();
+    find_cases(&dir, &mut files);
+    assert!(!files.is_empty(), "no test cases in {}", dir.display());
+    files.sort();
+
+    let mut failures = Vec::new();
+    for path in files {
+        let json = fs::read_to_string(&path).unwrap();
+        let cases: Vec<Case> = serde_json::from_str(&json).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+
+        let mut failed = mismatches(&cases, variant);
+        if !failed.is_empty() {
+            let count = failed.len();
+            failed.truncate(SHOWN);
+            failures.push(format!("{}: {count} failed\n  {}", path.display(), failed.join("\n  ")));
+        }
+    }
+
+    assert!(failures.is_empty(), "{} opcodes failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests suite in tests/data/65x02/"]
+fn nes6502() {
+    suite("nes6502", Variant::RP2A03);
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests suite in tests/data/65x02/"]
+fn nmos6502() {
+    suite("6502", Variant::NMOS6502);
+}
+
+// a few cases in the suite's format, so the harness itself is checked on every run
+const SAMPLE: &str = r#"[
+    {
+        "name": "a9 42 00",
+        "initial": { "pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                     "ram": [[1024, 169], [1025, 66]] },
+        "final": { "pc": 1026, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36,
+                   "ram": [[1024, 169], [1025, 66]] },
+        "cycles": [[1024, 169, "read"], [1025, 66, "read"]]
+    },
+    {
+        "name": "bd ff 02",
+        "initial": { "pc": 1024, "s": 253, "a": 0, "x": 1, "y": 0, "p": 38,
+                     "ram": [[1024, 189], [1025, 255], [1026, 2], [512, 17], [768, 128]] },
+        "final": { "pc": 1027, "s": 253, "a": 128, "x": 1, "y": 0, "p": 164,
+                   "ram": [[1024, 189], [1025, 255], [1026, 2], [512, 17], [768, 128]] },
+        "cycles": [[1024, 189, "read"], [1025, 255, "read"], [1026, 2, "read"],
+                   [512, 17, "read"], [768, 128, "read"]]
+    },
+    {
+        "name": "48 ea 00",
+        "initial": { "pc": 1024, "s": 253, "a": 90, "x": 0, "y": 0, "p": 36,
+                     "ram": [[1024, 72], [1025, 234]] },
+        "final": { "pc": 1025, "s": 252, "a": 90, "x": 0, "y": 0, "p": 36,
+                   "ram": [[1024, 72], [1025, 234], [509, 90]] },
+        "cycles": [[1024, 72, "read"], [1025, 234, "read"], [509, 90, "write"]]
+    }
+]"#;
+
+#[test]
+fn the_harness_runs_cases_in_the_suite_format() {
+    let cases: Vec<Case> = serde_json::from_str(SAMPLE).unwrap();
+    for case in &cases {
+        assert_eq!(run(case, Variant::RP2A03), Some(Ok(())), "{}", case.name);
+    }
+
+    // and it notices when the CPU disagrees
+    let mut wrong: Vec<Case> = serde_json::from_str(SAMPLE).unwrap();
+    wrong[0].expected.a = 0x43;
+    let report = run(&wrong[0], Variant::RP2A03).unwrap().unwrap_err();
+    assert!(report.contains("A 42, expected 43"), "{report}");
+}
+
+#[test]
+fn a_jam_case_does_not_hide_the_cases_after_it() {
+    let mut cases: Vec<Case> = serde_json::from_str(SAMPLE).unwrap();
+    cases[0].initial.ram[0].1 = 0x02;
+    cases[2].expected.a = 0x00;
+
+    let failed = mismatches(&cases, Variant::RP2A03);
+    assert_eq!(failed.len(), 1, "{failed:?}");
+    assert!(failed[0].starts_with("48 ea 00"), "{failed:?}");
+}
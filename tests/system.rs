@@ -0,0 +1,56 @@
+use std::thread;
+
+use rs6502::cartridge::Cartridge;
+use rs6502::nes::Nes;
+use rs6502::screenshot::{capture, Frame, Input};
+
+const START: u8 = 0x10;
+
+fn nestest() -> Nes {
+    let mut nes = Nes::new(Cartridge::new("nestest.nes".to_string()).unwrap());
+    nes.reset();
+    nes
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn the_machine_can_move_between_threads() {
+    assert_send::<Nes>();
+
+    let mut nes = nestest();
+    let here = capture(&mut nes.clone(), 30, &[]);
+    let there = thread::spawn(move || capture(&mut nes, 30, &[])).join().unwrap();
+    assert_eq!(here, there);
+}
+
+#[test]
+fn a_clone_is_a_save_state() {
+    let mut nes = nestest();
+    capture(&mut nes, 10, &[]);
+    let saved = nes.clone();
+
+    let script = [Input { frame: 0, buttons: START }, Input { frame: 2, buttons: 0x00 }];
+    let first = capture(&mut nes, 60, &script);
+
+    let mut replay = saved.clone();
+    let second = capture(&mut replay, 60, &script);
+    assert_eq!(first, second);
+    assert_eq!(nes.cpu.pc, replay.cpu.pc);
+    assert_eq!(nes.cpu.clock_count, replay.cpu.clock_count);
+
+    // and a branch that does something else goes its own way
+    let mut branch = saved;
+    let other = capture(&mut branch, 60, &[]);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn clones_leave_the_trace_behind() {
+    let mut nes = nestest();
+    nes.cpu.trace_to(std::io::sink());
+    let copy = nes.clone();
+    assert!(copy.cpu.trace.is_none());
+    assert!(!copy.cpu.trace_enabled);
+    assert_eq!(Frame::from_ppu(copy.ppu()), Frame::from_ppu(nes.ppu()));
+}
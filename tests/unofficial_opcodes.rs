@@ -0,0 +1,234 @@
+mod common;
+
+use common::{cpu_with_program, step};
+use rs6502::bus::CpuBus;
+use rs6502::cpu::{Status, CPU};
+use rs6502::memory::FlatMemory;
+
+fn flag(cpu: &CPU, s: Status) -> bool {
+    cpu.status.contains(s)
+}
+
+#[test]
+fn anc_copies_bit_7_into_carry() {
+    for opcode in [0x0B, 0x2B] {
+        let mut cpu = cpu_with_program(&[opcode, 0x80]);
+        cpu.a = 0xFF;
+        assert_eq!(step(&mut cpu), 2);
+        assert_eq!(cpu.a, 0x80);
+        assert!(flag(&cpu, Status::C));
+        assert!(flag(&cpu, Status::N));
+        assert!(!flag(&cpu, Status::Z));
+    }
+}
+
+#[test]
+fn alr_ands_then_shifts_right() {
+    let mut cpu = cpu_with_program(&[0x4B, 0x03]);
+    cpu.a = 0xFF;
+    assert_eq!(step(&mut cpu), 2);
+    assert_eq!(cpu.a, 0x01);
+    assert!(flag(&cpu, Status::C));
+    assert!(!flag(&cpu, Status::N));
+    assert!(!flag(&cpu, Status::Z));
+}
+
+#[test]
+fn arr_sets_carry_and_overflow_from_bits_6_and_5() {
+    let mut cpu = cpu_with_program(&[0x6B, 0xC0]);
+    cpu.a = 0xFF;
+    cpu.status.set(Status::C, true);
+    assert_eq!(step(&mut cpu), 2);
+    assert_eq!(cpu.a, 0xE0);
+    assert!(flag(&cpu, Status::N));
+    assert!(flag(&cpu, Status::C));
+    assert!(!flag(&cpu, Status::V));
+
+    let mut cpu = cpu_with_program(&[0x6B, 0x40]);
+    cpu.a = 0xFF;
+    cpu.status.set(Status::C, false);
+    step(&mut cpu);
+    assert_eq!(cpu.a, 0x20);
+    assert!(!flag(&cpu, Status::C));
+    assert!(flag(&cpu, Status::V));
+}
+
+#[test]
+fn axs_subtracts_from_a_and_x_without_borrow() {
+    let mut cpu = cpu_with_program(&[0xCB, 0x10]);
+    cpu.a = 0xF0;
+    cpu.x = 0x3C;
+    cpu.status.set(Status::C, false);
+    assert_eq!(step(&mut cpu), 2);
+    assert_eq!(cpu.x, 0x20);
+    assert_eq!(cpu.a, 0xF0);
+    assert!(flag(&cpu, Status::C));
+
+    let mut cpu = cpu_with_program(&[0xCB, 0x40]);
+    cpu.a = 0xF0;
+    cpu.x = 0x3C;
+    step(&mut cpu);
+    assert_eq!(cpu.x, 0xF0);
+    assert!(!flag(&cpu, Status::C));
+    assert!(flag(&cpu, Status::N));
+}
+
+#[test]
+fn las_ands_memory_with_sp_into_a_x_and_sp() {
+    let mut cpu = cpu_with_program(&[0xBB, 0x00, 0x03]);
+    cpu.bus.cpu_write(0x0300, 0x3C);
+    cpu.sp = 0xF0;
+    assert_eq!(step(&mut cpu), 4);
+    assert_eq!(cpu.a, 0x30);
+    assert_eq!(cpu.x, 0x30);
+    assert_eq!(cpu.sp, 0x30);
+}
+
+#[test]
+fn las_takes_an_extra_cycle_on_page_cross() {
+    let mut cpu = cpu_with_program(&[0xBB, 0xFF, 0x02]);
+    cpu.bus.cpu_write(0x0300, 0x0F);
+    cpu.y = 0x01;
+    cpu.sp = 0xFF;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.a, 0x0F);
+}
+
+#[test]
+fn xaa_uses_magic_constant() {
+    let mut cpu = cpu_with_program(&[0x8B, 0xFF]);
+    cpu.a = 0x00;
+    cpu.x = 0xFF;
+    assert_eq!(step(&mut cpu), 2);
+    assert_eq!(cpu.a, 0xEE);
+    assert!(flag(&cpu, Status::N));
+}
+
+#[test]
+fn ahx_stores_a_and_x_and_high_byte_plus_one() {
+    let mut cpu = cpu_with_program(&[0x9F, 0x00, 0x03]);
+    cpu.a = 0xFF;
+    cpu.x = 0x0F;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0300), 0x04);
+
+    let mut cpu = cpu_with_program(&[0x93, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x00);
+    cpu.bus.cpu_write(0x0011, 0x06);
+    cpu.a = 0xFF;
+    cpu.x = 0xFF;
+    cpu.y = 0x02;
+    assert_eq!(step(&mut cpu), 6);
+    assert_eq!(cpu.peek(0x0602), 0x07);
+}
+
+#[test]
+fn sya_stores_y_and_high_byte_plus_one() {
+    let mut cpu = cpu_with_program(&[0x9C, 0x00, 0x05]);
+    cpu.y = 0xFF;
+    cpu.x = 0x01;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0501), 0x06);
+}
+
+#[test]
+fn tas_sets_sp_to_a_and_x() {
+    // TAS $0600,Y: A & X is 0x37, the stored value 0x37 & (0x06 + 1)
+    let mut cpu = cpu_with_program(&[0x9B, 0x00, 0x06]);
+    cpu.a = 0xF7;
+    cpu.x = 0x3F;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.sp, 0x37);
+    assert_eq!(cpu.peek(0x0600), 0x07);
+}
+
+#[test]
+fn sxa_replaces_high_byte_on_page_cross() {
+    let mut cpu = cpu_with_program(&[0x9E, 0xFF, 0x02]);
+    cpu.x = 0x01;
+    cpu.y = 0x01;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0100), 0x01);
+    assert_eq!(cpu.peek(0x0300), 0x00);
+}
+
+#[test]
+fn sya_replaces_high_byte_on_page_cross() {
+    let mut cpu = cpu_with_program(&[0x9C, 0xF0, 0x04]);
+    cpu.y = 0x01;
+    cpu.x = 0x20;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0110), 0x01);
+    assert_eq!(cpu.peek(0x0510), 0x00);
+}
+
+#[test]
+fn oam_dma_halts_the_cpu_and_fills_oam() {
+    // LDA #$03, STA $4014, NOP
+    let mut cpu = cpu_with_program(&[0xA9, 0x03, 0x8D, 0x14, 0x40, 0xEA]);
+    for i in 0..=0xFF {
+        cpu.bus.cpu_write(0x0300 + i, i as u8);
+    }
+
+    step(&mut cpu);
+    assert_eq!(step(&mut cpu), 4);
+    let dma_cycles = step(&mut cpu) - 2;
+    assert!(dma_cycles == 513 || dma_cycles == 514);
+
+    cpu.bus.cpu_write(0x2003, 0x10);
+    assert_eq!(cpu.read(0x2004), 0x10);
+    // attribute bytes lose bits 2-4
+    cpu.bus.cpu_write(0x2003, 0x12);
+    assert_eq!(cpu.read(0x2004), 0x12 & 0xE3);
+}
+
+// flat memory that has a sprite DMA halt the CPU on one chosen read
+struct DmaOnRead {
+    mem: FlatMemory,
+    reads: usize,
+    halt_on: usize,
+}
+
+impl CpuBus for DmaOnRead {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.reads += 1;
+        self.mem.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.mem.write(addr, data);
+    }
+
+    fn peek(&mut self, addr: u16) -> u8 {
+        self.mem.peek(addr)
+    }
+
+    fn oam_dma(&mut self) -> Option<u8> {
+        (self.reads + 1 == self.halt_on).then_some(0x07)
+    }
+}
+
+#[test]
+fn dma_before_the_write_drops_the_and_with_the_high_byte() {
+    // SHY $1E00,X: the value is Y & 0x1F, unless DMA stalls the dummy read before the write
+    let run = |dma: bool| {
+        let mut mem = FlatMemory::from_rom(&[0x9C, 0x00, 0x1E]).unwrap();
+        mem.write(0x1E10, 0x00);
+        let mut cpu = CPU::new(DmaOnRead { mem, reads: 0, halt_on: 0 });
+        cpu.reset();
+        cpu.x = 0x10;
+        cpu.y = 0xF5;
+        if dma {
+            // opcode, operand lo and hi, then the dummy read
+            cpu.bus.halt_on = cpu.bus.reads + 4;
+        }
+        let cycles = step(&mut cpu);
+        (cycles, cpu.peek(0x1E10))
+    };
+
+    assert_eq!(run(false), (5, 0x15));
+
+    let (cycles, stored) = run(true);
+    assert!(cycles > 5 + 512, "{cycles} cycles");
+    assert_eq!(stored, 0xF5);
+}
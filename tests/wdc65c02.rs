@@ -0,0 +1,232 @@
+mod common;
+
+use common::{cpu_with_program, step, PROGRAM_START};
+use rs6502::cpu::{State, Status, Variant, CPU};
+
+fn cmos(program: &[u8]) -> CPU {
+    let mut cpu = cpu_with_program(program);
+    cpu.variant = Variant::WDC65C02;
+    cpu
+}
+
+#[test]
+fn bra_always_branches() {
+    let mut cpu = cmos(&[0x80, 0x02]);
+    assert_eq!(step(&mut cpu), 3);
+    assert_eq!(cpu.pc, PROGRAM_START + 4);
+}
+
+#[test]
+fn phx_and_ply_move_index_registers_through_the_stack() {
+    let mut cpu = cmos(&[0xDA, 0x7A]);
+    cpu.x = 0x42;
+    assert_eq!(step(&mut cpu), 3);
+    assert_eq!(step(&mut cpu), 4);
+    assert_eq!(cpu.y, 0x42);
+    assert_eq!(cpu.sp, 0xFD);
+}
+
+#[test]
+fn stz_stores_zero() {
+    let mut cpu = cmos(&[0x64, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0xFF);
+    assert_eq!(step(&mut cpu), 3);
+    assert_eq!(cpu.peek(0x0010), 0x00);
+}
+
+#[test]
+fn tsb_and_trb_set_and_reset_bits_from_a() {
+    let mut cpu = cmos(&[0x04, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x0F);
+    cpu.a = 0x30;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0010), 0x3F);
+    assert!(cpu.status.contains(Status::Z));
+
+    let mut cpu = cmos(&[0x14, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x3F);
+    cpu.a = 0x03;
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0010), 0x3C);
+    assert!(!cpu.status.contains(Status::Z));
+}
+
+#[test]
+fn zero_page_indirect_addressing() {
+    let mut cpu = cmos(&[0xB2, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x00);
+    cpu.bus.cpu_write(0x0011, 0x03);
+    cpu.bus.cpu_write(0x0300, 0x55);
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.a, 0x55);
+}
+
+#[test]
+fn bit_immediate_only_sets_z() {
+    let mut cpu = cmos(&[0x89, 0xC0]);
+    cpu.a = 0x01;
+    assert_eq!(step(&mut cpu), 2);
+    assert!(cpu.status.contains(Status::Z));
+    assert!(!cpu.status.contains(Status::N));
+    assert!(!cpu.status.contains(Status::V));
+}
+
+#[test]
+fn inc_and_dec_accumulator() {
+    let mut cpu = cmos(&[0x1A, 0x3A, 0x3A]);
+    cpu.a = 0xFF;
+    assert_eq!(step(&mut cpu), 2);
+    assert_eq!(cpu.a, 0x00);
+    assert!(cpu.status.contains(Status::Z));
+    step(&mut cpu);
+    step(&mut cpu);
+    assert_eq!(cpu.a, 0xFE);
+    assert!(cpu.status.contains(Status::N));
+}
+
+#[test]
+fn jmp_indirect_crosses_pages() {
+    let mut cpu = cmos(&[0x6C, 0xFF, 0x02]);
+    cpu.bus.cpu_write(0x02FF, 0x34);
+    cpu.bus.cpu_write(0x0300, 0x12);
+    assert_eq!(step(&mut cpu), 6);
+    assert_eq!(cpu.pc, 0x1234);
+
+    // the NMOS part still fetches the high byte from the start of the page
+    let mut cpu = cpu_with_program(&[0x6C, 0xFF, 0x02]);
+    cpu.bus.cpu_write(0x02FF, 0x34);
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.pc, 0x6C34);
+}
+
+#[test]
+fn jmp_absolute_indexed_indirect() {
+    let mut cpu = cmos(&[0x7C, 0x00, 0x03]);
+    cpu.bus.cpu_write(0x0302, 0x78);
+    cpu.bus.cpu_write(0x0303, 0x56);
+    cpu.x = 0x02;
+    assert_eq!(step(&mut cpu), 6);
+    assert_eq!(cpu.pc, 0x5678);
+}
+
+#[test]
+fn bbr_and_bbs_test_a_zero_page_bit() {
+    // BBR2: bit 2 is set, fall through
+    let mut cpu = cmos(&[0x2F, 0x10, 0x05]);
+    cpu.bus.cpu_write(0x0010, 0x04);
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.pc, PROGRAM_START + 3);
+
+    // BBS2: taken
+    let mut cpu = cmos(&[0xAF, 0x10, 0x05]);
+    cpu.bus.cpu_write(0x0010, 0x04);
+    assert_eq!(step(&mut cpu), 6);
+    assert_eq!(cpu.pc, PROGRAM_START + 8);
+}
+
+#[test]
+fn rmb_and_smb_change_a_single_bit() {
+    let mut cpu = cmos(&[0x37, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0xFF);
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0010), 0xF7);
+
+    let mut cpu = cmos(&[0xC7, 0x10]);
+    cpu.bus.cpu_write(0x0010, 0x00);
+    assert_eq!(step(&mut cpu), 5);
+    assert_eq!(cpu.peek(0x0010), 0x10);
+}
+
+#[test]
+fn undefined_opcodes_are_nops_of_the_right_length() {
+    // (program, bytes, cycles)
+    let cases: [(&[u8], u16, usize); 7] = [
+        (&[0x03], 1, 1),
+        (&[0xFB], 1, 1),
+        (&[0x02, 0x00], 2, 2),
+        (&[0x44, 0x00], 2, 3),
+        (&[0x54, 0x00], 2, 4),
+        (&[0xDC, 0x00, 0x00], 3, 4),
+        (&[0x5C, 0x00, 0x00], 3, 8),
+    ];
+
+    for (program, bytes, cycles) in cases {
+        let mut cpu = cmos(program);
+        cpu.a = 0x12;
+        assert_eq!(step(&mut cpu), cycles, "opcode {:02X}", program[0]);
+        assert_eq!(cpu.pc, PROGRAM_START + bytes, "opcode {:02X}", program[0]);
+        assert_eq!(cpu.a, 0x12);
+        assert!(!cpu.halted());
+    }
+}
+
+#[test]
+fn wai_sleeps_until_an_interrupt() {
+    let mut cpu = cmos(&[0xCB, 0xEA]);
+    assert_eq!(step(&mut cpu), 3);
+    for _ in 0..10 {
+        cpu.clock();
+    }
+    assert_eq!(cpu.state, State::Waiting);
+    assert_eq!(cpu.pc, PROGRAM_START + 1);
+
+    cpu.nmi();
+    assert_eq!(cpu.state, State::Running);
+}
+
+#[test]
+fn stp_stops_the_clock() {
+    let mut cpu = cmos(&[0xDB, 0xEA]);
+    assert_eq!(step(&mut cpu), 3);
+    assert!(cpu.halted());
+
+    cpu.nmi();
+    assert!(cpu.halted());
+}
+
+#[test]
+fn interrupts_clear_decimal_mode() {
+    let mut cpu = cmos(&[0x00, 0x00]);
+    cpu.status.set(Status::D, true);
+    step(&mut cpu);
+    assert!(!cpu.status.contains(Status::D));
+
+    let mut cpu = cmos(&[0xEA]);
+    cpu.status.set(Status::D, true);
+    cpu.nmi();
+    assert!(!cpu.status.contains(Status::D));
+
+    let mut cpu = cpu_with_program(&[0x00, 0x00]);
+    cpu.variant = Variant::NMOS6502;
+    cpu.status.set(Status::D, true);
+    step(&mut cpu);
+    assert!(cpu.status.contains(Status::D));
+}
+
+#[test]
+fn decimal_mode_sets_valid_flags_and_costs_a_cycle() {
+    let mut cpu = cmos(&[0x69, 0x01]);
+    cpu.a = 0x99;
+    cpu.status.set(Status::D, true);
+    assert_eq!(step(&mut cpu), 3);
+    assert_eq!(cpu.a, 0x00);
+    assert!(cpu.status.contains(Status::C));
+    assert!(cpu.status.contains(Status::Z));
+    assert!(!cpu.status.contains(Status::N));
+
+    let mut cpu = cmos(&[0xE9, 0x01]);
+    cpu.a = 0x00;
+    cpu.status.set(Status::D, true);
+    cpu.status.set(Status::C, true);
+    assert_eq!(step(&mut cpu), 3);
+    assert_eq!(cpu.a, 0x99);
+    assert!(!cpu.status.contains(Status::C));
+    assert!(cpu.status.contains(Status::N));
+
+    let mut cpu = cmos(&[0xE9, 0x13]);
+    cpu.a = 0x40;
+    cpu.status.set(Status::D, true);
+    cpu.status.set(Status::C, true);
+    step(&mut cpu);
+    assert_eq!(cpu.a, 0x27);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{cpu_with_program, step};
+use rs6502::bus::CpuBus;
+use rs6502::cpu::CPU;
+use rs6502::input::Zapper;
+
+const LIGHT_OFF: u8 = 0x08;
+const TRIGGER: u8 = 0x10;
+
+// Draws the whole screen in the given backdrop color and spins, with a Zapper in port 2
+// aimed at (100, 100). Rendering is off, so the address is moved back out of the palette
+// or the PPU would draw the entry it points at.
+fn zapper_cpu(color: u8) -> CPU {
+    #[rustfmt::skip]
+    let mut cpu = cpu_with_program(&[
+        0xA9, 0x3F,             // 0200  LDA #$3F
+        0x8D, 0x06, 0x20,       // 0202  STA $2006
+        0xA9, 0x00,             // 0205  LDA #$00
+        0x8D, 0x06, 0x20,       // 0207  STA $2006
+        0xA9, color,            // 020A  LDA #color
+        0x8D, 0x07, 0x20,       // 020C  STA $2007
+        0xA9, 0x00,             // 020F  LDA #$00
+        0x8D, 0x06, 0x20,       // 0211  STA $2006
+        0x8D, 0x06, 0x20,       // 0214  STA $2006
+        0x4C, 0x17, 0x02,       // 0217  JMP $0217
+    ]);
+    let mut zapper = Zapper::new();
+    zapper.aim = Some((100, 100));
+    cpu.bus.plug(1, Box::new(zapper));
+    cpu
+}
+
+fn run_to_scanline(cpu: &mut CPU, scanline: i32) {
+    while cpu.bus.ppu_position().0 != scanline {
+        step(cpu);
+    }
+}
+
+fn read(cpu: &mut CPU) -> u8 {
+    cpu.bus.cpu_read(0x4017, false) & 0x1F
+}
+
+#[test]
+fn light_is_seen_only_after_the_beam_passes_and_before_it_fades() {
+    let mut cpu = zapper_cpu(0x30);
+
+    run_to_scanline(&mut cpu, 90);
+    assert_eq!(read(&mut cpu), LIGHT_OFF);
+
+    run_to_scanline(&mut cpu, 105);
+    assert_eq!(read(&mut cpu), 0x00);
+
+    run_to_scanline(&mut cpu, 130);
+    assert_eq!(read(&mut cpu), LIGHT_OFF);
+}
+
+#[test]
+fn dark_pixels_are_not_light() {
+    let mut cpu = zapper_cpu(0x0F);
+    run_to_scanline(&mut cpu, 105);
+    assert_eq!(read(&mut cpu), LIGHT_OFF);
+}
+
+#[test]
+fn aiming_off_screen_sees_nothing() {
+    let mut cpu = zapper_cpu(0x30);
+    cpu.bus.device_mut::<Zapper>(1).unwrap().aim = None;
+    run_to_scanline(&mut cpu, 105);
+    assert_eq!(read(&mut cpu), LIGHT_OFF);
+}
+
+#[test]
+fn the_trigger_is_d4() {
+    let mut cpu = zapper_cpu(0x0F);
+    cpu.bus.device_mut::<Zapper>(1).unwrap().trigger = true;
+    assert_eq!(read(&mut cpu), TRIGGER | LIGHT_OFF);
+}
+